@@ -0,0 +1,113 @@
+//! Averages of a statistic over the orbit, for the averaging colorings.
+//!
+//! Every point $z_n$ of an escaping orbit contributes a term $t(z_n)$ in
+//! `0..=1`. The mean of the terms jumps when the escape count changes, so
+//! [`OrbitAverage::interpolate`] blends the means with and without the last
+//! term by the fractional part of the [`smooth_count`](crate::escape::smooth_count),
+//! which makes the coloring continuous across the escape bands.
+
+use crate::escape::ComplexDouble;
+
+/// Stripe density of [`Statistic::Stripe`] by default.
+pub const DEFAULT_STRIPE_DENSITY: f64 = 5.;
+
+/// Quantity averaged over the orbit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Statistic {
+    /// $\frac{1}{2}\sin(d \arg z) + \frac{1}{2}$ for the stripe density $d$,
+    /// which draws $d$ stripes around the set.
+    Stripe { density: f64 },
+    /// Where $|z_n|$ lies between the bounds the triangle inequality sets
+    /// from the previous point, $\big||z_n - c| - |c|\big|$ and
+    /// $|z_n - c| + |c|$, where $c$ is the constant added at every step.
+    TriangleInequality,
+}
+
+impl Statistic {
+    /// Term of the orbit point `z` obtained by adding the constant `c`, or
+    /// `None` where it isn't defined.
+    pub fn term(&self, z: ComplexDouble, c: ComplexDouble) -> Option<f64> {
+        match *self {
+            Statistic::Stripe { density } => Some(0.5 * (density * z.arg()).sin() + 0.5),
+            Statistic::TriangleInequality => {
+                let (power, constant) = ((z - c).norm(), c.norm());
+                let low = (power - constant).abs();
+                let high = power + constant;
+                (high > low).then(|| (z.norm() - low) / (high - low))
+            }
+        }
+    }
+}
+
+/// Running sum of the terms of an orbit.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OrbitAverage {
+    pub sum: f64,
+    /// Number of terms.
+    pub count: u32,
+    /// Last term.
+    pub last: f64,
+}
+
+impl OrbitAverage {
+    /// Sum after the orbit went on to `z`, obtained by adding `c`.
+    pub fn update(self, statistic: &Statistic, z: ComplexDouble, c: ComplexDouble) -> Self {
+        match statistic.term(z, c) {
+            Some(term) => OrbitAverage {
+                sum: self.sum + term,
+                count: self.count + 1,
+                last: term,
+            },
+            None => self,
+        }
+    }
+
+    /// Mean of the terms blended with the mean without the last term, by the
+    /// weight `fraction` in `0..=1` of the last one; `None` without terms.
+    pub fn interpolate(&self, fraction: f64) -> Option<f64> {
+        let mean = self.sum / self.count as f64;
+        let previous = match self.count {
+            0 => return None,
+            1 => mean,
+            count => (self.sum - self.last) / (count - 1) as f64,
+        };
+        Some(previous + fraction.clamp(0., 1.) * (mean - previous))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn term_test() {
+        let c = ComplexDouble::new(0.3, 0.4);
+        let stripe = Statistic::Stripe { density: 2. };
+        let i = ComplexDouble::new(0., 1.);
+        assert!((stripe.term(i, c).unwrap() - 0.5).abs() < 1e-12);
+        assert!((stripe.term(i + 1., c).unwrap() - 1.).abs() < 1e-12);
+
+        //  Points in the same direction as c from z² reach the upper bound
+        let triangle = Statistic::TriangleInequality;
+        assert_eq!(triangle.term(c * 3., c), Some(1.));
+        assert_eq!(triangle.term(c * -1., c), Some(0.));
+        assert_eq!(triangle.term(c, c), None);
+    }
+
+    #[test]
+    fn interpolate_test() {
+        let statistic = Statistic::Stripe { density: 1. };
+        let orbit = [1., -1., 0.2].map(|angle: f64| ComplexDouble::from_polar(2., angle));
+        let average = orbit.iter().fold(OrbitAverage::default(), |average, &z| {
+            average.update(&statistic, z, ComplexDouble::new(0., 0.))
+        });
+        let terms = orbit.map(|z| 0.5 * z.arg().sin() + 0.5);
+
+        assert_eq!(average.count, 3);
+        let mean = terms.iter().sum::<f64>() / 3.;
+        assert!((average.interpolate(1.).unwrap() - mean).abs() < 1e-12);
+        let previous = (terms[0] + terms[1]) / 2.;
+        assert!((average.interpolate(0.).unwrap() - previous).abs() < 1e-12);
+        assert_eq!(OrbitAverage::default().interpolate(0.5), None);
+    }
+}
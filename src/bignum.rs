@@ -0,0 +1,631 @@
+//! Numbers of arbitrary precision.
+//!
+//! [`Decimal`] stores coordinates exactly as written in scenes and on the
+//! command line, however many digits deep-zoom locations have.
+//!
+//! [`Fixed`] holds enough bits after the binary point to compute orbits of
+//! points that `f64` can't tell apart, as needed by the
+//! [perturbation](crate::perturbation) renderer. There is a single 64-bit
+//! integer limb in front of the binary point, plenty for orbits that stop at
+//! the escape radius.
+
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
+
+use num::complex::Complex;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Complex number type with exact decimal parts.
+pub type ComplexDecimal = Complex<Decimal>;
+
+/// Decimal number with any number of digits.
+///
+/// Decimals are parsed from and written as strings like `-0.745`, `1e-100`
+/// or `0.1234…`, and are written back exactly as parsed, up to leading and
+/// trailing zeros. Scenes store them as strings, but also accept plain
+/// numbers.
+#[derive(Clone, PartialEq)]
+pub struct Decimal {
+    negative: bool,
+    /// Significant digits, most significant first, without leading or
+    /// trailing zeros; empty for zero.
+    digits: Vec<u8>,
+    /// Power of ten of the last digit.
+    exponent: i64,
+    /// Closest `f64`, computed once as most users only need that.
+    approximation: f64,
+}
+
+impl Decimal {
+    /// Decimal made of `digits` with the last one at the power of ten
+    /// `exponent`, or `None` if the position of its decimal point doesn't
+    /// fit an `i64` once leading and trailing zeros are dropped.
+    fn new(negative: bool, mut digits: Vec<u8>, mut exponent: i64) -> Option<Self> {
+        let leading = digits.iter().take_while(|&&digit| digit == 0).count();
+        digits.drain(..leading);
+        while digits.last() == Some(&0) {
+            digits.pop();
+            exponent = exponent.checked_add(1)?;
+        }
+        if digits.is_empty() {
+            exponent = 0;
+        }
+        //  Formatting counts the position of the decimal point
+        exponent.checked_add(digits.len() as i64)?;
+
+        let mut decimal = Decimal {
+            negative: negative && !digits.is_empty(),
+            digits,
+            exponent,
+            approximation: 0.,
+        };
+        let scientific: String = decimal
+            .digits
+            .iter()
+            .map(|&digit| (b'0' + digit) as char)
+            .collect();
+        decimal.approximation = format!(
+            "{}{}e{}",
+            if decimal.negative { "-" } else { "" },
+            if scientific.is_empty() {
+                "0"
+            } else {
+                &scientific
+            },
+            decimal.exponent
+        )
+        .parse()
+        .expect("digits and exponent form a float");
+        Some(decimal)
+    }
+
+    /// Shortest decimal that rounds to `x`.
+    ///
+    /// # Panics
+    ///
+    /// If `x` isn't finite.
+    pub fn from_f64(x: f64) -> Self {
+        assert!(x.is_finite(), "{} is not finite", x);
+        format!("{:e}", x)
+            .parse()
+            .expect("floats format as decimals")
+    }
+
+    /// Closest `f64`, which may be infinite or zero for decimals out of its
+    /// range.
+    pub fn to_f64(&self) -> f64 {
+        self.approximation
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+}
+
+impl Default for Decimal {
+    fn default() -> Self {
+        Decimal::new(false, Vec::new(), 0).expect("zero is a decimal")
+    }
+}
+
+/// Error parsing a [`Decimal`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDecimalError {
+    input: String,
+}
+
+impl fmt::Display for ParseDecimalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}` is not a decimal number", self.input)
+    }
+}
+
+impl Error for ParseDecimalError {}
+
+impl FromStr for Decimal {
+    type Err = ParseDecimalError;
+
+    /// Parses an optional sign, digits with an optional decimal point and
+    /// an optional exponent, e.g. `-1.25e-30`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseDecimalError {
+            input: s.to_string(),
+        };
+        let (negative, unsigned) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => (mantissa, exponent.parse().map_err(|_| error())?),
+            None => (unsigned, 0i64),
+        };
+        let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+        let all_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+        if integer.len() + fraction.len() == 0 || !all_digits(integer) || !all_digits(fraction) {
+            return Err(error());
+        }
+        let digits = integer
+            .bytes()
+            .chain(fraction.bytes())
+            .map(|byte| byte - b'0')
+            .collect();
+        let exponent = exponent
+            .checked_sub(fraction.len() as i64)
+            .ok_or_else(error)?;
+        Decimal::new(negative, digits, exponent).ok_or_else(error)
+    }
+}
+
+impl fmt::Display for Decimal {
+    /// Plain notation for numbers of moderate size, scientific notation for
+    /// others, as in `0.000123` and `1.23e-7`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let digits: String = self
+            .digits
+            .iter()
+            .map(|&digit| (b'0' + digit) as char)
+            .collect();
+        //  Position of the decimal point, counted from the first digit
+        let point = digits.len() as i64 + self.exponent;
+        if self.exponent >= 0 && point <= 21 {
+            write!(f, "{}{}", digits, "0".repeat(self.exponent as usize))
+        } else if point > 0 && self.exponent < 0 {
+            let (integer, fraction) = digits.split_at(point as usize);
+            write!(f, "{}.{}", integer, fraction)
+        } else if point > -6 && point <= 0 {
+            write!(f, "0.{}{}", "0".repeat(-point as usize), digits)
+        } else {
+            let (first, rest) = digits.split_at(1);
+            match rest.is_empty() {
+                true => write!(f, "{}e{}", first, point - 1),
+                false => write!(f, "{}.{}e{}", first, rest, point - 1),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Decimal({})", self)
+    }
+}
+
+impl Serialize for Decimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DecimalVisitor;
+
+        impl Visitor<'_> for DecimalVisitor {
+            type Value = Decimal;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a decimal number or a string holding one")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Decimal, E> {
+                s.parse().map_err(E::custom)
+            }
+
+            fn visit_f64<E: de::Error>(self, x: f64) -> Result<Decimal, E> {
+                match x.is_finite() {
+                    true => Ok(Decimal::from_f64(x)),
+                    false => Err(E::custom(format!("{} is not finite", x))),
+                }
+            }
+
+            fn visit_i64<E: de::Error>(self, x: i64) -> Result<Decimal, E> {
+                Ok(x.to_string().parse().expect("integers are decimals"))
+            }
+
+            fn visit_u64<E: de::Error>(self, x: u64) -> Result<Decimal, E> {
+                Ok(x.to_string().parse().expect("integers are decimals"))
+            }
+        }
+
+        deserializer.deserialize_any(DecimalVisitor)
+    }
+}
+
+const LIMB_BITS: u32 = u64::BITS;
+
+/// Signed fixed-point number with a given number of bits after the point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixed {
+    negative: bool,
+    /// Magnitude, least significant limb first; the last limb is the integer
+    /// part.
+    limbs: Vec<u64>,
+}
+
+impl Fixed {
+    /// Zero with at least `precision` bits after the point.
+    pub fn zero(precision: u32) -> Self {
+        Fixed {
+            negative: false,
+            limbs: vec![0; precision.div_ceil(LIMB_BITS) as usize + 1],
+        }
+    }
+
+    /// Number of bits after the point.
+    pub fn precision(&self) -> u32 {
+        self.fraction_limbs() as u32 * LIMB_BITS
+    }
+
+    fn fraction_limbs(&self) -> usize {
+        self.limbs.len() - 1
+    }
+
+    /// `x` with at least `precision` bits after the point, rounded towards
+    /// zero if it has more.
+    ///
+    /// # Panics
+    ///
+    /// If `x` isn't finite or its integer part doesn't fit in 64 bits.
+    pub fn from_f64(x: f64, precision: u32) -> Self {
+        assert!(
+            x.is_finite() && x.abs() < 2f64.powi(64),
+            "{} out of range",
+            x
+        );
+        let mut result = Fixed::zero(precision);
+        if x == 0. {
+            return result;
+        }
+
+        //  x = mantissa * 2^exponent with an integer mantissa
+        let bits = x.abs().to_bits();
+        let biased = (bits >> 52) as i64;
+        let (mantissa, exponent) = match biased {
+            0 => (bits & ((1 << 52) - 1), -1074),
+            _ => ((bits & ((1 << 52) - 1)) | (1 << 52), biased - 1075),
+        };
+
+        //  Bit position of the mantissa's lowest bit in the limbs
+        let shift = exponent + (result.fraction_limbs() as i64 * LIMB_BITS as i64);
+        let wide = mantissa as u128;
+        for (i, limb) in result.limbs.iter_mut().enumerate() {
+            let offset = i as i64 * LIMB_BITS as i64 - shift;
+            *limb = match offset {
+                offset if offset <= -(LIMB_BITS as i64) || offset >= 128 => 0,
+                offset if offset < 0 => (wide << -offset) as u64,
+                offset => (wide >> offset) as u64,
+            };
+        }
+        result.negative = x < 0.;
+        result
+    }
+
+    /// `x` with at least `precision` bits after the point, rounded towards
+    /// zero if it has more, or `None` if its integer part doesn't fit in 64
+    /// bits.
+    pub fn from_decimal(x: &Decimal, precision: u32) -> Option<Self> {
+        let mut result = Fixed::zero(precision);
+        let fraction = result.fraction_limbs();
+
+        //  |x| * 2^(64 fraction) as an integer: the digits, shifted by whole
+        //  limbs, then scaled by the power of ten
+        let mut scaled = vec![0u64; fraction];
+        let mut integer = Vec::new();
+        for &digit in &x.digits {
+            multiply_small(&mut integer, 10);
+            add_small(&mut integer, digit as u64);
+        }
+        scaled.extend(integer);
+        for _ in 0..x.exponent.max(0) {
+            multiply_small(&mut scaled, 10);
+            if scaled.len() > fraction + 1 {
+                return None;
+            }
+        }
+        for _ in x.exponent..0 {
+            divide_small(&mut scaled, 10);
+            if scaled.is_empty() {
+                break;
+            }
+        }
+
+        if scaled.len() > fraction + 1 {
+            return None;
+        }
+        result.limbs[..scaled.len()].copy_from_slice(&scaled);
+        result.negative = x.negative && !result.is_zero();
+        Some(result)
+    }
+
+    /// Closest `f64`, up to rounding of the last bit.
+    pub fn to_f64(&self) -> f64 {
+        let top = match self.limbs.iter().rposition(|&limb| limb != 0) {
+            Some(top) => top,
+            None => return 0.,
+        };
+        let magnitude: f64 = (top.saturating_sub(2)..=top)
+            .map(|i| {
+                let exponent = (i as i32 - self.fraction_limbs() as i32) * LIMB_BITS as i32;
+                //  In two steps, so that limbs below 2^-1022 don't vanish
+                self.limbs[i] as f64 * 2f64.powi(exponent / 2) * 2f64.powi(exponent - exponent / 2)
+            })
+            .sum();
+        match self.negative {
+            true => -magnitude,
+            false => magnitude,
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.limbs.iter().all(|&limb| limb == 0)
+    }
+
+    fn compare_magnitude(&self, other: &Fixed) -> Ordering {
+        self.limbs.iter().rev().cmp(other.limbs.iter().rev())
+    }
+
+    /// Sum of `self` and `other` if `negate` is false, else their difference.
+    fn add_signed(&self, other: &Fixed, negate: bool) -> Fixed {
+        assert_eq!(self.limbs.len(), other.limbs.len(), "precisions differ");
+        let other_negative = other.negative != negate;
+        if self.negative == other_negative {
+            let mut carry = false;
+            let limbs = self
+                .limbs
+                .iter()
+                .zip(&other.limbs)
+                .map(|(&a, &b)| {
+                    let (sum, c1) = a.overflowing_add(b);
+                    let (sum, c2) = sum.overflowing_add(carry as u64);
+                    carry = c1 || c2;
+                    sum
+                })
+                .collect();
+            debug_assert!(!carry, "integer part overflows");
+            return Fixed {
+                negative: self.negative,
+                limbs,
+            };
+        }
+
+        //  Subtract the smaller magnitude from the larger one
+        let (large, small, negative) = match self.compare_magnitude(other) {
+            Ordering::Less => (other, self, other_negative),
+            _ => (self, other, self.negative),
+        };
+        let mut borrow = false;
+        let limbs = large
+            .limbs
+            .iter()
+            .zip(&small.limbs)
+            .map(|(&a, &b)| {
+                let (difference, b1) = a.overflowing_sub(b);
+                let (difference, b2) = difference.overflowing_sub(borrow as u64);
+                borrow = b1 || b2;
+                difference
+            })
+            .collect();
+        let mut result = Fixed { negative, limbs };
+        result.negative &= !result.is_zero();
+        result
+    }
+}
+
+/// Multiplies the integer with the little-endian `limbs` by `factor`.
+fn multiply_small(limbs: &mut Vec<u64>, factor: u64) {
+    let mut carry = 0u128;
+    for limb in limbs.iter_mut() {
+        let product = *limb as u128 * factor as u128 + carry;
+        *limb = product as u64;
+        carry = product >> LIMB_BITS;
+    }
+    if carry != 0 {
+        limbs.push(carry as u64);
+    }
+}
+
+/// Adds `term` to the integer with the little-endian `limbs`.
+fn add_small(limbs: &mut Vec<u64>, term: u64) {
+    let mut carry = term;
+    for limb in limbs.iter_mut() {
+        let (sum, overflow) = limb.overflowing_add(carry);
+        *limb = sum;
+        carry = overflow as u64;
+    }
+    if carry != 0 {
+        limbs.push(carry);
+    }
+}
+
+/// Divides the integer with the little-endian `limbs` by `divisor`, rounding
+/// towards zero and dropping leading zero limbs.
+fn divide_small(limbs: &mut Vec<u64>, divisor: u64) {
+    let mut remainder = 0u128;
+    for limb in limbs.iter_mut().rev() {
+        let dividend = (remainder << LIMB_BITS) | *limb as u128;
+        *limb = (dividend / divisor as u128) as u64;
+        remainder = dividend % divisor as u128;
+    }
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+}
+
+impl Add for &Fixed {
+    type Output = Fixed;
+
+    fn add(self, other: &Fixed) -> Fixed {
+        self.add_signed(other, false)
+    }
+}
+
+impl Sub for &Fixed {
+    type Output = Fixed;
+
+    fn sub(self, other: &Fixed) -> Fixed {
+        self.add_signed(other, true)
+    }
+}
+
+impl Mul for &Fixed {
+    type Output = Fixed;
+
+    /// Product rounded towards zero.
+    fn mul(self, other: &Fixed) -> Fixed {
+        assert_eq!(self.limbs.len(), other.limbs.len(), "precisions differ");
+        let n = self.limbs.len();
+        let mut product = vec![0u64; 2 * n];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u128;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let sum = a as u128 * b as u128 + product[i + j] as u128 + carry;
+                product[i + j] = sum as u64;
+                carry = sum >> LIMB_BITS;
+            }
+            product[i + n] = carry as u64;
+        }
+
+        let fraction = self.fraction_limbs();
+        debug_assert!(
+            product[fraction + n..].iter().all(|&limb| limb == 0),
+            "integer part overflows"
+        );
+        let mut result = Fixed {
+            negative: self.negative != other.negative,
+            limbs: product[fraction..fraction + n].to_vec(),
+        };
+        result.negative &= !result.is_zero();
+        result
+    }
+}
+
+impl Neg for &Fixed {
+    type Output = Fixed;
+
+    fn neg(self) -> Fixed {
+        Fixed {
+            negative: !self.negative && !self.is_zero(),
+            limbs: self.limbs.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f64_test() {
+        for x in [0., 1., -1.5, 0.1, -3e-30, 1e15 + 0.5, 5e-324] {
+            assert_eq!(Fixed::from_f64(x, 1100).to_f64(), x);
+        }
+        //  Bits beyond the precision are cut off
+        assert_eq!(Fixed::from_f64(-1e-30, 64).to_f64(), 0.);
+        assert_eq!(Fixed::from_f64(0.75, 100).precision(), 128);
+    }
+
+    #[test]
+    fn decimal_test() {
+        for (input, output) in [
+            ("-0.745", "-0.745"),
+            ("+007.50", "7.5"),
+            ("-0.000", "0"),
+            ("12e3", "12000"),
+            (".5", "0.5"),
+            ("1E-100", "1e-100"),
+            ("0.00012", "0.00012"),
+            ("1.5e25", "1.5e25"),
+        ] {
+            assert_eq!(input.parse::<Decimal>().unwrap().to_string(), output);
+        }
+        let max = i64::MAX;
+        for invalid in [
+            "",
+            "-",
+            "1.2.3",
+            "e5",
+            "0x10",
+            "1e",
+            " 1",
+            &format!("10e{}", max),
+            &format!("1e{}", max),
+            &format!("1e{}0", max),
+        ] {
+            assert!(invalid.parse::<Decimal>().is_err(), "{}", invalid);
+        }
+
+        //  Every digit survives a round trip
+        let long = "-0.7436438870371587047521915061147741957222074935184367303";
+        let decimal: Decimal = long.parse().unwrap();
+        assert_eq!(decimal.to_string(), long);
+        assert_eq!(decimal.to_f64(), -0.7436438870371587);
+        assert_eq!(Decimal::from_f64(0.1).to_string(), "0.1");
+        assert_eq!(Decimal::from_f64(-3e-300).to_f64(), -3e-300);
+
+        //  Extreme exponents that still fit
+        let huge: Decimal = format!("1e{}", max - 1).parse().unwrap();
+        assert_eq!(huge.to_string(), format!("1e{}", max - 1));
+        let tiny: Decimal = format!("100e{}", i64::MIN).parse().unwrap();
+        assert_eq!(tiny.to_string(), format!("1e{}", i64::MIN + 2));
+        assert_eq!(
+            format!("0e{}", max).parse::<Decimal>().unwrap(),
+            Decimal::default()
+        );
+    }
+
+    #[test]
+    fn from_decimal_test() {
+        let fixed =
+            |s: &str, precision| Fixed::from_decimal(&s.parse().unwrap(), precision).unwrap();
+        assert_eq!(fixed("-1.25", 64), Fixed::from_f64(-1.25, 64));
+        assert_eq!(fixed("3e2", 64).to_f64(), 300.);
+        assert_eq!(fixed("1e-30", 64), Fixed::zero(64));
+
+        //  Digits beyond f64 are kept
+        let precise = fixed("0.1000000000000000000000000000001", 256);
+        assert_eq!((&precise - &fixed("0.1", 256)).to_f64(), 1e-31);
+
+        //  The integer part has 64 bits
+        let largest = fixed("-18446744073709551615.75", 64);
+        assert_eq!(largest.to_f64(), -18446744073709551615.75);
+        for s in ["18446744073709551616", "-1e20", "1e999999999"] {
+            assert_eq!(Fixed::from_decimal(&s.parse().unwrap(), 64), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn arithmetic_test() {
+        let fixed = |x| Fixed::from_f64(x, 128);
+        let (a, b) = (fixed(1.25), fixed(-0.375));
+        assert_eq!((&a + &b).to_f64(), 0.875);
+        assert_eq!((&b - &a).to_f64(), -1.625);
+        assert_eq!((&b + &fixed(0.375)), fixed(0.));
+        assert_eq!((&a * &b).to_f64(), -0.46875);
+        assert_eq!((&b * &b).to_f64(), 0.140625);
+        assert_eq!((-&a).to_f64(), -1.25);
+
+        //  Bits far below the resolution of f64 survive
+        let tiny = Fixed::from_f64(1e-60, 256);
+        let one = Fixed::from_f64(1., 256);
+        let sum = &one + &tiny;
+        assert_eq!(sum.to_f64(), 1.);
+        assert_eq!((&sum - &one).to_f64(), 1e-60);
+        assert_eq!((&(&sum * &sum) - &one).to_f64(), 2e-60);
+    }
+}
@@ -0,0 +1,164 @@
+//! Plain two-dimensional pixel buffers.
+
+use std::num::NonZeroUsize;
+use std::ops::Index;
+use std::sync::Mutex;
+use std::thread;
+
+use plotters::style::RGBColor;
+
+/// Grid of `width * height` values stored row by row, top row first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Buffer<T> {
+    width: u32,
+    height: u32,
+    data: Vec<T>,
+}
+
+impl<T: Clone> Buffer<T> {
+    /// Buffer with every value set to `value`.
+    pub fn new(width: u32, height: u32, value: T) -> Self {
+        Buffer {
+            width,
+            height,
+            data: vec![value; width as usize * height as usize],
+        }
+    }
+}
+
+impl<T> Buffer<T> {
+    /// Buffer holding `data` row by row.
+    ///
+    /// Panics if `data` doesn't have `width * height` values.
+    pub fn from_vec(width: u32, height: u32, data: Vec<T>) -> Self {
+        assert_eq!(
+            data.len(),
+            width as usize * height as usize,
+            "buffer data doesn't match its size"
+        );
+        Buffer {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// `(width, height)` of the buffer.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// All values, row by row.
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Iterates over the rows, top row first.
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        self.data.chunks(self.width.max(1) as usize)
+    }
+
+    /// Iterates over all values together with their `(x, y)` position.
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32, &T)> {
+        (0..)
+            .zip(self.rows())
+            .flat_map(|(y, row)| (0..).zip(row).map(move |(x, value)| (x, y, value)))
+    }
+
+    /// Buffer of the same size holding `f` of every value.
+    pub fn map<U, G: FnMut(&T) -> U>(&self, f: G) -> Buffer<U> {
+        Buffer {
+            width: self.width,
+            height: self.height,
+            data: self.data.iter().map(f).collect(),
+        }
+    }
+}
+
+impl<T: Send> Buffer<T> {
+    /// Calls `fill(y, row)` for every row on `threads` threads, `0` meaning
+    /// one per available core.
+    ///
+    /// Rows are handed out one at a time, so rows that take long to compute
+    /// don't hold up the others. The result doesn't depend on the number of
+    /// threads as long as `fill` only looks at its own row.
+    pub fn fill_rows<G: Fn(u32, &mut [T]) + Sync>(&mut self, threads: usize, fill: G) {
+        let threads = match threads {
+            0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            threads => threads,
+        };
+        let rows = Mutex::new(self.data.chunks_mut(self.width.max(1) as usize).zip(0..));
+
+        thread::scope(|scope| {
+            for _ in 0..threads.min(self.height as usize) {
+                scope.spawn(|| loop {
+                    let next = rows.lock().unwrap().next();
+                    let Some((row, y)) = next else { break };
+                    fill(y, row);
+                });
+            }
+        });
+    }
+}
+
+impl<T> Index<(u32, u32)> for Buffer<T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (u32, u32)) -> &T {
+        assert!(
+            x < self.width && y < self.height,
+            "pixel ({}, {}) is outside the buffer",
+            x,
+            y
+        );
+        &self.data[y as usize * self.width as usize + x as usize]
+    }
+}
+
+impl Buffer<RGBColor> {
+    /// Pixels as consecutive red, green and blue bytes, the layout expected by
+    /// most image encoders.
+    pub fn to_rgb_bytes(&self) -> Vec<u8> {
+        self.data
+            .iter()
+            .flat_map(|&RGBColor(r, g, b)| [r, g, b])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_test() {
+        let mut buffer = Buffer::new(3, 2, 0);
+        buffer.fill_rows(2, |y, row| {
+            for (x, value) in (0..).zip(row) {
+                *value = 10 * y + x;
+            }
+        });
+
+        assert_eq!(buffer.as_slice(), &[0, 1, 2, 10, 11, 12]);
+        assert_eq!(buffer[(2, 1)], 12);
+        assert_eq!(buffer.pixels().nth(4), Some((1, 1, &11)));
+        assert_eq!(
+            buffer.map(|value| value % 2).as_slice(),
+            &[0, 1, 0, 0, 1, 0]
+        );
+    }
+
+    #[test]
+    fn rgb_bytes_test() {
+        let buffer = Buffer::new(2, 1, RGBColor(1, 2, 3));
+        assert_eq!(buffer.to_rgb_bytes(), vec![1, 2, 3, 1, 2, 3]);
+    }
+}
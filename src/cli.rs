@@ -0,0 +1,893 @@
+//! Command-line arguments of the `mandelbrot` binary.
+
+use std::ops::Range;
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+
+use mandelbrot::average::DEFAULT_STRIPE_DENSITY;
+use mandelbrot::bignum::{ComplexDecimal, Decimal, ParseDecimalError};
+use mandelbrot::color::{Coloring, InteriorColoring, DEFAULT_FIELD_LINES, DEFAULT_THICKNESS};
+use mandelbrot::escape::{ComplexDouble, DEFAULT_ESCAPE_RADIUS};
+use mandelbrot::fractal::{FractalKind, MIN_POWER};
+use mandelbrot::lighting::Lighting;
+use mandelbrot::palette::{ColorSpace, Gradient};
+use mandelbrot::precision::Precision;
+use mandelbrot::render::{plotting_size, MAX_SUPERSAMPLING};
+use mandelbrot::sampling::SamplePattern;
+use mandelbrot::scene::Scene;
+use mandelbrot::trap::{OrbitTrap, TrapImage};
+use mandelbrot::view::{View, DEFAULT_RADIUS};
+
+/// Names of the built-in fractals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FractalName {
+    Mandelbrot,
+    Julia,
+    BurningShip,
+    Tricorn,
+    Multibrot,
+}
+
+/// Names of the colorings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColoringName {
+    EscapeTime,
+    Smooth,
+    Histogram,
+    Distance,
+    StripeAverage,
+    TriangleAverage,
+    BinaryDecomposition,
+    ExternalAngle,
+    FieldLines,
+}
+
+/// Names of the interior colorings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InteriorName {
+    Black,
+    Distance,
+    Period,
+    Multiplier,
+    MultiplierAngle,
+    FinalAngle,
+}
+
+/// Names of the orbit trap shapes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TrapName {
+    Point,
+    Line,
+    Cross,
+    Circle,
+    Image,
+}
+
+/// Names of the built-in gradients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PaletteName {
+    Hue,
+    Viridis,
+    Magma,
+    Inferno,
+    Plasma,
+}
+
+/// Names of the color spaces gradients are mixed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SpaceName {
+    Srgb,
+    LinearRgb,
+    Oklab,
+}
+
+/// Names of the layouts of the samples inside a pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SamplePatternName {
+    Grid,
+    RotatedGrid,
+    Jitter,
+}
+
+/// Names of the number types points are iterated with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PrecisionName {
+    Auto,
+    F32,
+    F64,
+    DoubleDouble,
+}
+
+/// Renders the Mandelbrot set and its relatives into an image file.
+///
+/// Without `--scene` every option starts from its default and the classic
+/// full view of the set is drawn. With `--scene` the options override the
+/// corresponding settings of the scene file.
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    /// Scene file (.toml or .json) to start from
+    #[arg(long)]
+    pub scene: Option<PathBuf>,
+
+    /// Print the effective scene as TOML instead of rendering it
+    #[arg(long)]
+    pub dump_scene: bool,
+
+    /// Fractal to draw [default: mandelbrot]
+    #[arg(long, value_enum)]
+    pub fractal: Option<FractalName>,
+
+    /// Parameter `c` of the Julia set, as `RE,IM`
+    #[arg(
+        long,
+        value_name = "RE,IM",
+        allow_hyphen_values = true,
+        value_parser = parse_complex,
+        required_if_eq("fractal", "julia"),
+        requires = "fractal",
+    )]
+    pub julia_c: Option<ComplexDouble>,
+
+    /// Degree `d` of the Multibrot set `z^d + c`
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u32).range(MIN_POWER as i64..),
+        required_if_eq("fractal", "multibrot"),
+        requires = "fractal",
+    )]
+    pub power: Option<u32>,
+
+    /// Point at the center of the image, as `RE,IM`, keeping every digit
+    #[arg(long, value_name = "RE,IM", allow_hyphen_values = true, value_parser = parse_decimal_complex)]
+    pub center: Option<ComplexDecimal>,
+
+    /// Magnification relative to the full view of the set
+    #[arg(long, value_parser = parse_positive)]
+    pub zoom: Option<f64>,
+
+    /// Half the height of the view, as an exact decimal instead of a zoom
+    #[arg(long, value_parser = parse_decimal_positive, conflicts_with = "zoom")]
+    pub radius: Option<Decimal>,
+
+    /// Window of the complex plane to draw, as `RE_MIN,RE_MAX,IM_MIN,IM_MAX`
+    #[arg(
+        long,
+        value_name = "RE_MIN,RE_MAX,IM_MIN,IM_MAX",
+        allow_hyphen_values = true,
+        value_parser = parse_bounds,
+        conflicts_with_all = ["center", "zoom", "radius"],
+    )]
+    pub bounds: Option<(Range<f64>, Range<f64>)>,
+
+    /// Image width in pixels, including the axis frame [default: 1600]
+    #[arg(long)]
+    pub width: Option<u32>,
+
+    /// Image height in pixels, including the axis frame [default: 1200]
+    #[arg(long)]
+    pub height: Option<u32>,
+
+    /// Maximum number of iterations per point [default: 100]
+    #[arg(short = 'n', long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub iterations: Option<u32>,
+
+    /// Radius of the circle an orbit has to leave to count as escaped [default: 2]
+    #[arg(long, value_parser = parse_escape_radius)]
+    pub escape_radius: Option<f64>,
+
+    /// How escape counts are mapped onto the palette [default: escape-time]
+    #[arg(long, value_enum)]
+    pub coloring: Option<ColoringName>,
+
+    /// Number of stripes of the stripe-average coloring [default: 5]
+    #[arg(long, allow_hyphen_values = true, value_parser = parse_finite)]
+    pub stripe_density: Option<f64>,
+
+    /// Number of field lines of the field-lines coloring [default: 2]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub field_lines: Option<u32>,
+
+    /// How points inside the set are colored [default: black]
+    #[arg(long, value_enum)]
+    pub interior: Option<InteriorName>,
+
+    /// Color points by how close their orbits come to a shape, or by the
+    /// pixel of `--trap-image` their orbits land on
+    #[arg(long, value_enum)]
+    pub trap: Option<TrapName>,
+
+    /// Center of the orbit trap, as `RE,IM` [default: 0,0]
+    #[arg(
+        long,
+        value_name = "RE,IM",
+        allow_hyphen_values = true,
+        value_parser = parse_complex,
+        requires = "trap",
+    )]
+    pub trap_center: Option<ComplexDouble>,
+
+    /// Angle of line and cross traps, in degrees [default: 0]
+    #[arg(long, allow_hyphen_values = true, value_parser = parse_finite, requires = "trap")]
+    pub trap_angle: Option<f64>,
+
+    /// Radius of circle traps or width of image traps [default: 1]
+    #[arg(long, value_parser = parse_positive, requires = "trap")]
+    pub trap_size: Option<f64>,
+
+    /// PNG file of image traps
+    #[arg(long, required_if_eq("trap", "image"), requires = "trap")]
+    pub trap_image: Option<PathBuf>,
+
+    /// Shade the outside of the set as a relief lit from `--light-azimuth`
+    /// (fractals with a derivative only). The other light options also
+    /// adjust the lighting of a scene that has one
+    #[arg(long)]
+    pub lighting: bool,
+
+    /// Direction the light comes from, in degrees counterclockwise from the
+    /// real axis [default: 45]
+    #[arg(long, allow_hyphen_values = true, value_parser = parse_finite)]
+    pub light_azimuth: Option<f64>,
+
+    /// Angle of the light above the plane, from 0 to 90 degrees [default: 45]
+    #[arg(long, value_parser = parse_finite)]
+    pub light_elevation: Option<f64>,
+
+    /// Height of the surface normals; higher is flatter [default: 1]
+    #[arg(long, value_parser = parse_positive)]
+    pub light_height: Option<f64>,
+
+    /// Brightness of the highlights, from 0 to 1 [default: 0.4]
+    #[arg(long, value_parser = parse_non_negative)]
+    pub specular: Option<f64>,
+
+    /// Exponent of the highlights; higher is sharper [default: 20]
+    #[arg(long, value_parser = parse_positive)]
+    pub shininess: Option<f64>,
+
+    /// Share of the lit color in the blend with the palette color, from 0
+    /// to 1 [default: 1]
+    #[arg(long, value_parser = parse_non_negative)]
+    pub light_strength: Option<f64>,
+
+    /// Built-in gradient of the palette [default: hue]
+    #[arg(long, value_enum)]
+    pub palette: Option<PaletteName>,
+
+    /// Fractint .map or GIMP .ggr file to take the gradient of the palette from
+    #[arg(long, conflicts_with = "palette")]
+    pub gradient: Option<PathBuf>,
+
+    /// Color space gradient colors are mixed in [default: srgb]
+    #[arg(long, value_enum)]
+    pub color_space: Option<SpaceName>,
+
+    /// Number of runs through the gradient from the outside to the set [default: 1]
+    #[arg(long, value_parser = parse_positive)]
+    pub palette_repeat: Option<f64>,
+
+    /// Shift of the gradient, in runs through it [default: 0]
+    #[arg(long, allow_hyphen_values = true, value_parser = parse_finite)]
+    pub palette_offset: Option<f64>,
+
+    /// Number of samples per pixel along each axis [default: 1]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=MAX_SUPERSAMPLING as i64))]
+    pub supersampling: Option<u32>,
+
+    /// Layout of the samples inside a supersampled pixel [default: grid]
+    #[arg(long, value_enum)]
+    pub sample_pattern: Option<SamplePatternName>,
+
+    /// Only supersample pixels that differ from a neighbour by more than
+    /// this, in a color channel or in palette position, from 0 to 1
+    #[arg(long, value_parser = parse_non_negative)]
+    pub adaptive_threshold: Option<f64>,
+
+    /// Seed of the random sample positions of the jitter pattern [default: 0]
+    #[arg(long)]
+    pub jitter_seed: Option<u64>,
+
+    /// Fill uniform rectangles without iterating their inside
+    #[arg(long)]
+    pub subdivide: bool,
+
+    /// Iterate pixels relative to a high-precision orbit of the center, for
+    /// zooms beyond 1e13 (Mandelbrot set only)
+    #[arg(long)]
+    pub perturbation: bool,
+
+    /// Number type the Mandelbrot set is iterated with; auto picks f64 or
+    /// double-double by zoom depth [default: auto]
+    #[arg(long, value_enum)]
+    pub precision: Option<PrecisionName>,
+
+    /// Iterate several points at a time with the vector instructions of the
+    /// CPU (Mandelbrot set with f64 only)
+    #[arg(long)]
+    pub simd: bool,
+
+    /// Number of threads computing pixels [default: one per core]
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub threads: Option<u64>,
+
+    /// Image file to write; the format follows the extension [default: mandelbrot.png]
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+impl Cli {
+    /// Scene described by the arguments.
+    pub fn scene(&self) -> Result<Scene, String> {
+        let mut scene = match &self.scene {
+            Some(path) => {
+                Scene::load(path).map_err(|err| format!("{}: {}", path.display(), err))?
+            }
+            None => Scene::default(),
+        };
+        let settings = &mut scene.render;
+
+        if let Some(fractal) = self.fractal {
+            settings.fractal = match fractal {
+                FractalName::Mandelbrot => FractalKind::Mandelbrot,
+                FractalName::Julia => FractalKind::Julia {
+                    c: self.julia_c.unwrap_or_default(),
+                },
+                FractalName::BurningShip => FractalKind::BurningShip,
+                FractalName::Tricorn => FractalKind::Tricorn,
+                FractalName::Multibrot => FractalKind::Multibrot {
+                    power: self.power.unwrap_or(MIN_POWER),
+                },
+            };
+        }
+
+        if let Some(width) = self.width {
+            settings.size.0 = width;
+        }
+        if let Some(height) = self.height {
+            settings.size.1 = height;
+        }
+        if let Some(iterations) = self.iterations {
+            settings.iterations = iterations;
+        }
+        if let Some(escape_radius) = self.escape_radius {
+            settings.escape_radius = escape_radius;
+        }
+        if let Some(coloring) = self.coloring {
+            settings.coloring = match coloring {
+                ColoringName::EscapeTime => Coloring::EscapeTime,
+                ColoringName::Smooth => Coloring::Smooth,
+                ColoringName::Histogram => Coloring::Histogram,
+                ColoringName::Distance => Coloring::Distance {
+                    thickness: DEFAULT_THICKNESS,
+                },
+                ColoringName::StripeAverage => Coloring::StripeAverage {
+                    density: DEFAULT_STRIPE_DENSITY,
+                },
+                ColoringName::TriangleAverage => Coloring::TriangleAverage,
+                ColoringName::BinaryDecomposition => Coloring::BinaryDecomposition,
+                ColoringName::ExternalAngle => Coloring::ExternalAngle,
+                ColoringName::FieldLines => Coloring::FieldLines {
+                    lines: DEFAULT_FIELD_LINES,
+                },
+            };
+        }
+        if let Some(stripe_density) = self.stripe_density {
+            match &mut settings.coloring {
+                Coloring::StripeAverage { density } => *density = stripe_density,
+                _ => return Err("--stripe-density needs the stripe-average coloring".to_string()),
+            }
+        }
+        if let Some(field_lines) = self.field_lines {
+            match &mut settings.coloring {
+                Coloring::FieldLines { lines } => *lines = field_lines,
+                _ => return Err("--field-lines needs the field-lines coloring".to_string()),
+            }
+        }
+        if let Some(interior) = self.interior {
+            settings.interior = match interior {
+                InteriorName::Black => InteriorColoring::Black,
+                InteriorName::Distance => InteriorColoring::Distance,
+                InteriorName::Period => InteriorColoring::Period,
+                InteriorName::Multiplier => InteriorColoring::Multiplier,
+                InteriorName::MultiplierAngle => InteriorColoring::MultiplierAngle,
+                InteriorName::FinalAngle => InteriorColoring::FinalAngle,
+            };
+        }
+        if let Some(trap) = self.trap {
+            let center = self.trap_center.unwrap_or_default();
+            let angle = self.trap_angle.unwrap_or_default();
+            let size = self.trap_size.unwrap_or(1.);
+            settings.trap = Some(match trap {
+                TrapName::Point => OrbitTrap::Point { center },
+                TrapName::Line => OrbitTrap::Line { center, angle },
+                TrapName::Cross => OrbitTrap::Cross { center, angle },
+                TrapName::Circle => OrbitTrap::Circle {
+                    center,
+                    radius: size,
+                },
+                TrapName::Image => {
+                    let path = self.trap_image.as_ref().expect("required by clap");
+                    let image = TrapImage::load(path)
+                        .map_err(|err| format!("{}: {}", path.display(), err))?;
+                    OrbitTrap::Image {
+                        image,
+                        center,
+                        width: size,
+                    }
+                }
+            });
+        }
+        if self.lighting {
+            settings.lighting.get_or_insert_with(Lighting::default);
+        }
+        let light_options = [
+            self.light_azimuth,
+            self.light_elevation,
+            self.light_height,
+            self.specular,
+            self.shininess,
+            self.light_strength,
+        ];
+        if let Some(lighting) = &mut settings.lighting {
+            if let Some(azimuth) = self.light_azimuth {
+                lighting.azimuth = azimuth;
+            }
+            if let Some(elevation) = self.light_elevation {
+                lighting.elevation = elevation;
+            }
+            if let Some(height) = self.light_height {
+                lighting.height = height;
+            }
+            if let Some(specular) = self.specular {
+                lighting.specular = specular;
+            }
+            if let Some(shininess) = self.shininess {
+                lighting.shininess = shininess;
+            }
+            if let Some(strength) = self.light_strength {
+                lighting.strength = strength;
+            }
+        } else if light_options.iter().any(Option::is_some) {
+            return Err("the light options need --lighting or a scene with lighting".to_string());
+        }
+        if let Some(palette) = self.palette {
+            settings.palette.gradient = match palette {
+                PaletteName::Hue => Gradient::Hue,
+                PaletteName::Viridis => Gradient::Viridis,
+                PaletteName::Magma => Gradient::Magma,
+                PaletteName::Inferno => Gradient::Inferno,
+                PaletteName::Plasma => Gradient::Plasma,
+            };
+        }
+        if let Some(path) = &self.gradient {
+            settings.palette.gradient =
+                Gradient::load(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        }
+        if let Some(space) = self.color_space {
+            settings.palette.space = match space {
+                SpaceName::Srgb => ColorSpace::Srgb,
+                SpaceName::LinearRgb => ColorSpace::LinearRgb,
+                SpaceName::Oklab => ColorSpace::Oklab,
+            };
+        }
+        if let Some(repeat) = self.palette_repeat {
+            settings.palette.repeat = repeat;
+        }
+        if let Some(offset) = self.palette_offset {
+            settings.palette.offset = offset;
+        }
+        if let Some(supersampling) = self.supersampling {
+            settings.supersampling = supersampling;
+        }
+        if let Some(threshold) = self.adaptive_threshold {
+            settings.adaptive_threshold = Some(threshold);
+        }
+        if let Some(pattern) = self.sample_pattern {
+            settings.sample_pattern = match pattern {
+                SamplePatternName::Grid => SamplePattern::Grid,
+                SamplePatternName::RotatedGrid => SamplePattern::RotatedGrid,
+                SamplePatternName::Jitter => SamplePattern::Jitter { seed: 0 },
+            };
+        }
+        if let Some(jitter_seed) = self.jitter_seed {
+            match &mut settings.sample_pattern {
+                SamplePattern::Jitter { seed } => *seed = jitter_seed,
+                _ => return Err("--jitter-seed needs the jitter sample pattern".to_string()),
+            }
+        }
+        if self.subdivide {
+            settings.subdivide = true;
+        }
+        if self.perturbation {
+            settings.perturbation = true;
+        }
+        if let Some(precision) = self.precision {
+            settings.precision = match precision {
+                PrecisionName::Auto => Precision::Auto,
+                PrecisionName::F32 => Precision::F32,
+                PrecisionName::F64 => Precision::F64,
+                PrecisionName::DoubleDouble => Precision::DoubleDouble,
+            };
+        }
+        if self.simd {
+            settings.simd = true;
+        }
+        if let Some(threads) = self.threads {
+            settings.threads = threads as usize;
+        }
+
+        if let Some((re, im)) = &self.bounds {
+            settings.view = View::new(re.clone(), im.clone());
+        } else if self.center.is_some() || self.zoom.is_some() || self.radius.is_some() {
+            // A new center or zoom keeps the other one from the scene, pixels
+            // become square.
+            let samples = plotting_size(settings.size);
+            let radius = match (&self.radius, self.zoom) {
+                (Some(radius), _) => radius.clone(),
+                (None, Some(zoom)) => Decimal::from_f64(DEFAULT_RADIUS / zoom),
+                (None, None) => settings.view.radius.clone(),
+            };
+            settings.view = View {
+                center: self.center.clone().unwrap_or(settings.view.center.clone()),
+                radius,
+                aspect: samples.0 as f64 / samples.1 as f64,
+            };
+        }
+
+        if let Some(output) = &self.output {
+            scene.output = output.clone();
+        }
+
+        scene.validate().map_err(|err| err.to_string())?;
+        Ok(scene)
+    }
+}
+
+/// Parses a comma separated list of exactly `N` finite numbers.
+fn parse_numbers<const N: usize>(s: &str) -> Result<[f64; N], String> {
+    let numbers = s
+        .split(',')
+        .map(|part| match part.trim().parse::<f64>() {
+            Ok(x) if x.is_finite() => Ok(x),
+            _ => Err(format!("`{}` is not a finite number", part.trim())),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    numbers.try_into().map_err(|numbers: Vec<f64>| {
+        format!(
+            "expected {} comma separated numbers, got {}",
+            N,
+            numbers.len()
+        )
+    })
+}
+
+fn parse_complex(s: &str) -> Result<ComplexDouble, String> {
+    let [re, im] = parse_numbers(s)?;
+    Ok(ComplexDouble::new(re, im))
+}
+
+fn parse_decimal_complex(s: &str) -> Result<ComplexDecimal, String> {
+    let parts = s
+        .split(',')
+        .map(|part| {
+            part.trim()
+                .parse::<Decimal>()
+                .map_err(|err| err.to_string())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    match <[Decimal; 2]>::try_from(parts) {
+        Ok([re, im]) => Ok(ComplexDecimal::new(re, im)),
+        Err(parts) => Err(format!(
+            "expected 2 comma separated numbers, got {}",
+            parts.len()
+        )),
+    }
+}
+
+fn parse_decimal_positive(s: &str) -> Result<Decimal, String> {
+    let x: Decimal = s
+        .trim()
+        .parse()
+        .map_err(|err: ParseDecimalError| err.to_string())?;
+    if x.is_negative() || x.is_zero() {
+        return Err(format!("{} is not positive", x));
+    }
+    Ok(x)
+}
+
+fn parse_bounds(s: &str) -> Result<(Range<f64>, Range<f64>), String> {
+    let [re_min, re_max, im_min, im_max] = parse_numbers(s)?;
+    if re_min >= re_max {
+        return Err(format!("real range {}..{} is empty", re_min, re_max));
+    }
+    if im_min >= im_max {
+        return Err(format!("imaginary range {}..{} is empty", im_min, im_max));
+    }
+    Ok((re_min..re_max, im_min..im_max))
+}
+
+fn parse_finite(s: &str) -> Result<f64, String> {
+    let [x] = parse_numbers(s)?;
+    Ok(x)
+}
+
+fn parse_positive(s: &str) -> Result<f64, String> {
+    let [x] = parse_numbers(s)?;
+    if x <= 0. {
+        return Err(format!("{} is not positive", x));
+    }
+    Ok(x)
+}
+
+fn parse_non_negative(s: &str) -> Result<f64, String> {
+    let [x] = parse_numbers(s)?;
+    if x < 0. {
+        return Err(format!("{} is negative", x));
+    }
+    Ok(x)
+}
+
+fn parse_escape_radius(s: &str) -> Result<f64, String> {
+    let [radius] = parse_numbers(s)?;
+    if radius < DEFAULT_ESCAPE_RADIUS {
+        return Err(format!(
+            "{} is smaller than {}, the radius beyond which every orbit diverges",
+            radius, DEFAULT_ESCAPE_RADIUS
+        ));
+    }
+    Ok(radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mandelbrot::palette::Palette;
+    use mandelbrot::render::Settings;
+
+    fn parse(args: &[&str]) -> Result<Scene, String> {
+        let cli =
+            Cli::try_parse_from(["mandelbrot"].iter().chain(args)).map_err(|e| e.to_string())?;
+        cli.scene()
+    }
+
+    #[test]
+    fn default_test() {
+        assert_eq!(parse(&[]), Ok(Scene::default()));
+    }
+
+    #[test]
+    fn view_test() {
+        let scene = parse(&[
+            "--width",
+            "250",
+            "--height",
+            "150",
+            "--center=-0.5,0.25",
+            "--zoom",
+            "2.4",
+        ])
+        .unwrap();
+        assert_eq!(
+            scene.render.view,
+            View::centered(ComplexDouble::new(-0.5, 0.25), 2.4, 2.)
+        );
+
+        let scene = parse(&["--bounds", "-2,1,-1,1"]).unwrap();
+        assert_eq!(scene.render.view, View::new(-2.0..1.0, -1.0..1.0));
+
+        //  Deep-zoom coordinates keep all their digits
+        let re = "-1.76877851023770025498824962680554152145";
+        let scene = parse(&[&format!("--center={}, 0", re), "--radius", "1e-30"]).unwrap();
+        assert_eq!(scene.render.view.center.re.to_string(), re);
+        assert_eq!(scene.render.view.radius.to_string(), "1e-30");
+        assert!(parse(&["--radius", "-1"]).is_err());
+        assert!(parse(&["--center", "1,2,3"]).is_err());
+    }
+
+    #[test]
+    fn fractal_test() {
+        let scene = parse(&["--fractal", "julia", "--julia-c=-0.8,0.156"]).unwrap();
+        assert_eq!(
+            scene.render.fractal,
+            FractalKind::Julia {
+                c: ComplexDouble::new(-0.8, 0.156)
+            }
+        );
+
+        let scene = parse(&["--fractal", "multibrot", "--power", "4"]).unwrap();
+        assert_eq!(scene.render.fractal, FractalKind::Multibrot { power: 4 });
+
+        let scene = parse(&["--fractal", "burning-ship"]).unwrap();
+        assert_eq!(scene.render.fractal, FractalKind::BurningShip);
+    }
+
+    #[test]
+    fn coloring_test() {
+        let scene = parse(&["--coloring", "smooth"]).unwrap();
+        assert_eq!(scene.render.coloring, Coloring::Smooth);
+
+        let scene = parse(&["--interior", "multiplier-angle"]).unwrap();
+        assert_eq!(scene.render.interior, InteriorColoring::MultiplierAngle);
+        assert!(parse(&["--coloring", "banded"]).is_err());
+
+        let scene = parse(&["--coloring", "stripe-average", "--stripe-density", "3"]).unwrap();
+        assert_eq!(
+            scene.render.coloring,
+            Coloring::StripeAverage { density: 3. }
+        );
+        assert!(parse(&["--stripe-density", "3"]).is_err());
+        assert!(parse(&["--coloring", "smooth", "--stripe-density", "3"]).is_err());
+
+        let scene = parse(&["--coloring", "field-lines", "--field-lines", "16"]).unwrap();
+        assert_eq!(scene.render.coloring, Coloring::FieldLines { lines: 16 });
+        assert!(parse(&["--coloring", "field-lines", "--field-lines", "0"]).is_err());
+        assert!(parse(&["--coloring", "smooth", "--field-lines", "4"]).is_err());
+    }
+
+    #[test]
+    fn lighting_test() {
+        let scene = parse(&["--lighting", "--light-azimuth", "-90", "--specular", "0"]).unwrap();
+        assert_eq!(
+            scene.render.lighting,
+            Some(Lighting {
+                azimuth: -90.,
+                specular: 0.,
+                ..Lighting::default()
+            })
+        );
+        assert_eq!(parse(&[]).unwrap().render.lighting, None);
+        assert!(parse(&["--light-height", "2"]).is_err());
+        assert!(parse(&["--lighting", "--light-elevation", "120"]).is_err());
+        assert!(parse(&["--lighting", "--light-strength", "2"]).is_err());
+
+        //  Light options adjust the lighting of a scene without --lighting
+        let path = std::env::temp_dir().join("mandelbrot_cli_lighting_test.toml");
+        std::fs::write(&path, "output = 'lit.png'\n[render.lighting]\nazimuth = 10").unwrap();
+        let scene = parse(&["--scene", path.to_str().unwrap(), "--light-strength", "0.5"]);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(
+            scene.unwrap().render.lighting,
+            Some(Lighting {
+                azimuth: 10.,
+                strength: 0.5,
+                ..Lighting::default()
+            })
+        );
+    }
+
+    #[test]
+    fn trap_test() {
+        let scene = parse(&[
+            "--trap",
+            "cross",
+            "--trap-center=-0.5,0.5",
+            "--trap-angle",
+            "30",
+        ]);
+        assert_eq!(
+            scene.unwrap().render.trap,
+            Some(OrbitTrap::Cross {
+                center: ComplexDouble::new(-0.5, 0.5),
+                angle: 30.
+            })
+        );
+
+        let path = std::env::temp_dir().join("mandelbrot_cli_trap_test.png");
+        image::RgbaImage::new(3, 2).save(&path).unwrap();
+        let scene = parse(&["--trap", "image", "--trap-image", path.to_str().unwrap()]);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            scene.unwrap().render.trap,
+            Some(OrbitTrap::Image { image, width, .. }) if image.path() == path && width == 1.
+        ));
+
+        assert!(parse(&["--trap", "image"]).is_err());
+        assert!(parse(&["--trap", "image", "--trap-image", "missing.png"]).is_err());
+        assert!(parse(&["--trap-size", "2"]).is_err());
+        assert!(parse(&["--trap", "circle", "--trap-size", "0"]).is_err());
+    }
+
+    #[test]
+    fn sample_pattern_test() {
+        let scene = parse(&["--sample-pattern", "jitter", "--jitter-seed", "5"]).unwrap();
+        assert_eq!(
+            scene.render.sample_pattern,
+            SamplePattern::Jitter { seed: 5 }
+        );
+        let scene = parse(&["--sample-pattern", "rotated-grid"]).unwrap();
+        assert_eq!(scene.render.sample_pattern, SamplePattern::RotatedGrid);
+        assert!(parse(&["--jitter-seed", "5"]).is_err());
+        assert!(parse(&["--sample-pattern", "grid", "--jitter-seed", "5"]).is_err());
+
+        let scene = parse(&["--supersampling", "3", "--adaptive-threshold", "0.1"]).unwrap();
+        assert_eq!(scene.render.adaptive_threshold, Some(0.1));
+        assert!(parse(&["--adaptive-threshold", "0.1"]).is_err());
+        assert!(parse(&["--adaptive-threshold", "-1"]).is_err());
+    }
+
+    #[test]
+    fn precision_test() {
+        let scene = parse(&["--precision", "double-double"]).unwrap();
+        assert_eq!(scene.render.precision, Precision::DoubleDouble);
+        assert!(parse(&["--precision", "f32", "--fractal", "tricorn"]).is_err());
+    }
+
+    #[test]
+    fn palette_test() {
+        let scene = parse(&[
+            "--palette",
+            "magma",
+            "--color-space",
+            "oklab",
+            "--palette-repeat",
+            "4",
+            "--palette-offset=-0.5",
+        ])
+        .unwrap();
+        assert_eq!(
+            scene.render.palette,
+            Palette {
+                gradient: Gradient::Magma,
+                space: ColorSpace::Oklab,
+                repeat: 4.,
+                offset: -0.5,
+            }
+        );
+
+        let path = std::env::temp_dir().join("mandelbrot_cli_palette_test.map");
+        std::fs::write(&path, "0 0 0\n255 255 255\n").unwrap();
+        let scene = parse(&["--gradient", path.to_str().unwrap()]);
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(
+            scene.unwrap().render.palette.gradient,
+            Gradient::Stops { stops } if stops.len() == 2
+        ));
+
+        assert!(parse(&["--gradient", "missing.map"]).is_err());
+        assert!(parse(&["--palette-repeat", "0"]).is_err());
+    }
+
+    #[test]
+    fn scene_test() {
+        let path = std::env::temp_dir().join("mandelbrot_cli_scene_test.toml");
+        std::fs::write(
+            &path,
+            "output = 'deep.bmp'\n[render]\niterations = 400\nsize = [800, 600]",
+        )
+        .unwrap();
+
+        let scene = parse(&["--scene", path.to_str().unwrap(), "--height", "500"]).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(scene.output, PathBuf::from("deep.bmp"));
+        assert_eq!(
+            scene.render,
+            Settings {
+                iterations: 400,
+                size: (800, 500),
+                ..Settings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn validation_test() {
+        assert!(parse(&["--bounds", "1,0,-1,1"]).is_err());
+        assert!(parse(&["--bounds", "0,1,-1"]).is_err());
+        assert!(parse(&["--bounds", "-2,1,-1,1", "--zoom", "2"]).is_err());
+        assert!(parse(&["--zoom", "0"]).is_err());
+        assert!(parse(&["--center", "0,nan"]).is_err());
+        assert!(parse(&["--iterations", "0"]).is_err());
+        assert!(parse(&["--escape-radius", "1.5"]).is_err());
+        assert!(parse(&["--supersampling", "0"]).is_err());
+        assert!(parse(&["--width", "50"]).is_err());
+        assert!(parse(&["-o", "mandelbrot.txt"]).is_err());
+        assert!(parse(&["--scene", "missing.toml"]).is_err());
+        assert!(parse(&["--fractal", "julia"]).is_err());
+        assert!(parse(&["--fractal", "multibrot", "--power", "1"]).is_err());
+        assert!(parse(&["--power", "3"]).is_err());
+    }
+}
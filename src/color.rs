@@ -0,0 +1,601 @@
+//! Mapping of escape counts to pixel colors.
+
+use std::f64::consts::PI;
+
+use plotters::style::{RGBColor, BLACK};
+use serde::{Deserialize, Serialize};
+
+use crate::average::{Statistic, DEFAULT_STRIPE_DENSITY};
+use crate::escape::{
+    smooth_count, ComplexDouble, EscapeResult, Track, DEFAULT_ESCAPE_RADIUS, SMOOTH_ESCAPE_RADIUS,
+};
+use crate::palette::{ColorSpace, Palette};
+
+/// Default width of the darkened band along the boundary, in pixels.
+pub const DEFAULT_THICKNESS: f64 = 1.;
+
+fn default_thickness() -> f64 {
+    DEFAULT_THICKNESS
+}
+
+/// Number of field lines of [`Coloring::FieldLines`] by default.
+pub const DEFAULT_FIELD_LINES: u32 = 2;
+
+fn default_field_lines() -> u32 {
+    DEFAULT_FIELD_LINES
+}
+
+fn default_stripe_density() -> f64 {
+    DEFAULT_STRIPE_DENSITY
+}
+
+/// Smallest escape radius of the averaging colorings, large enough for the
+/// averages to settle before the orbits escape.
+pub const AVERAGE_ESCAPE_RADIUS: f64 = 1e5;
+
+/// How the result of iterating a point is turned into a palette position.
+///
+/// Only escaping points are colored this way; points that never escaped are
+/// colored by an [`InteriorColoring`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Coloring {
+    /// Position proportional to the escape count.
+    #[default]
+    EscapeTime,
+    /// Position proportional to the [`smooth_count`], which removes the
+    /// bands between neighbouring escape counts.
+    Smooth,
+    /// Position given by the share of the render's escaping samples with a
+    /// lower [`smooth_count`], so that every part of the palette covers about
+    /// the same number of pixels however the counts are distributed.
+    Histogram,
+    /// Same position as [`Coloring::Smooth`], but points closer to the set
+    /// than `thickness` pixels are darkened by their
+    /// [exterior distance](EscapeResult::exterior_distance), which brings out
+    /// filaments thinner than a pixel. Fractals without a derivative aren't
+    /// darkened.
+    Distance {
+        #[serde(default = "default_thickness")]
+        thickness: f64,
+    },
+    /// Position given by the average over the orbit of $\sin(d \arg z)$,
+    /// see [`Statistic::Stripe`], which winds `density` stripes around the
+    /// set.
+    StripeAverage {
+        #[serde(default = "default_stripe_density")]
+        density: f64,
+    },
+    /// Position given by the average over the orbit of where $|z|$ lies
+    /// between the bounds of the triangle inequality, see
+    /// [`Statistic::TriangleInequality`].
+    TriangleAverage,
+    /// Same position as [`Coloring::EscapeTime`], but points whose orbit
+    /// escaped below the real axis are painted `BLACK`, which splits every
+    /// escape band into cells along the external rays.
+    BinaryDecomposition,
+    /// Position given by the angle of the point the orbit escaped to, which
+    /// runs once through the palette around every escape band.
+    ExternalAngle,
+    /// Same position as [`Coloring::Smooth`], darkened along `lines` field
+    /// lines: the external rays of angles `k / lines` at the outer edge of
+    /// every escape band, which branch into `degree` times as many towards
+    /// the set. The shading is continuous across the bands for fractals of
+    /// integer degree.
+    FieldLines {
+        #[serde(default = "default_field_lines")]
+        lines: u32,
+    },
+}
+
+impl Coloring {
+    /// Smallest escape radius the coloring works well with.
+    pub fn min_escape_radius(&self) -> f64 {
+        match self {
+            Coloring::EscapeTime => DEFAULT_ESCAPE_RADIUS,
+            Coloring::Smooth
+            | Coloring::Histogram
+            | Coloring::Distance { .. }
+            | Coloring::BinaryDecomposition
+            | Coloring::ExternalAngle
+            | Coloring::FieldLines { .. } => SMOOTH_ESCAPE_RADIUS,
+            Coloring::StripeAverage { .. } | Coloring::TriangleAverage => AVERAGE_ESCAPE_RADIUS,
+        }
+    }
+
+    /// Statistic the coloring averages over the orbit, if any.
+    pub fn statistic(&self) -> Option<Statistic> {
+        match *self {
+            Coloring::StripeAverage { density } => Some(Statistic::Stripe { density }),
+            Coloring::TriangleAverage => Some(Statistic::TriangleInequality),
+            _ => None,
+        }
+    }
+
+    /// Quantities the coloring needs tracked along the orbits.
+    pub fn track(&self) -> Track {
+        Track {
+            derivative: matches!(self, Coloring::Distance { .. }),
+            average: self.statistic(),
+            ..Track::default()
+        }
+    }
+
+    /// Checks the parameters of the coloring.
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            Coloring::Distance { thickness } if !(thickness > 0. && thickness.is_finite()) => Err(
+                format!("distance thickness {} is not a positive number", thickness),
+            ),
+            Coloring::StripeAverage { density } if !density.is_finite() => {
+                Err(format!("stripe density {} is not a finite number", density))
+            }
+            Coloring::FieldLines { lines: 0 } => Err("no field lines".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Escape value of a point with the given `result`, the quantity the
+    /// palette position derives from, or `None` if it never escaped.
+    pub fn value(&self, result: &EscapeResult, context: &ColorContext) -> Option<f64> {
+        if !result.escaped() {
+            return None;
+        }
+
+        let smooth = || {
+            smooth_count(
+                result.count,
+                result.z,
+                context.escape_radius,
+                context.degree,
+            )
+        };
+        match self {
+            Coloring::EscapeTime | Coloring::BinaryDecomposition => Some(result.count as f64),
+            Coloring::Smooth
+            | Coloring::Histogram
+            | Coloring::Distance { .. }
+            | Coloring::FieldLines { .. } => Some(smooth()),
+            Coloring::ExternalAngle => Some((result.z.arg() / (2. * PI)).rem_euclid(1.)),
+            Coloring::StripeAverage { .. } | Coloring::TriangleAverage => {
+                //  The smooth count lies between count - 1 and count
+                let fraction = smooth() - (result.count as f64 - 1.);
+                result.average?.interpolate(fraction)
+            }
+        }
+    }
+
+    /// Distribution of the escape values of `results`, the samples of a
+    /// whole render, if the coloring needs it for [`ColorContext::histogram`].
+    pub fn histogram<'a, I: IntoIterator<Item = &'a EscapeResult>>(
+        &self,
+        results: I,
+        context: &ColorContext,
+    ) -> Option<Histogram> {
+        if *self != Coloring::Histogram {
+            return None;
+        }
+        let values = results
+            .into_iter()
+            .filter_map(|result| self.value(result, context))
+            .collect();
+        Some(Histogram::new(values))
+    }
+
+    /// Palette position in `0..=1` of a point with the given `result`, or
+    /// `None` if it never escaped or its orbit had nothing to average.
+    pub fn position(&self, result: &EscapeResult, context: &ColorContext) -> Option<f64> {
+        let value = self.value(result, context)?;
+        let t = match (self, &context.histogram) {
+            (Coloring::Histogram, Some(histogram)) => histogram.rank(value),
+            (
+                Coloring::StripeAverage { .. }
+                | Coloring::TriangleAverage
+                | Coloring::ExternalAngle,
+                _,
+            ) => value,
+            _ => value / context.num_iterations as f64,
+        };
+        Some(t.clamp(0., 1.))
+    }
+
+    /// Color of a point, see [`Coloring::position`].
+    pub fn color(
+        &self,
+        palette: &Palette,
+        result: &EscapeResult,
+        context: &ColorContext,
+    ) -> RGBColor {
+        let Some(t) = self.position(result, context) else {
+            return BLACK;
+        };
+        let color = palette.color(t);
+
+        match (self, result.exterior_distance()) {
+            (Coloring::Distance { thickness }, Some(distance)) => {
+                let shade = distance / (thickness * context.pixel_size);
+                ColorSpace::Srgb.mix(BLACK, color, shade.clamp(0., 1.))
+            }
+            (Coloring::BinaryDecomposition, _) if result.z.im < 0. => BLACK,
+            (Coloring::FieldLines { lines }, _) => {
+                let shade = field_line_shade(*lines, result, context);
+                ColorSpace::Srgb.mix(BLACK, color, shade)
+            }
+            _ => color,
+        }
+    }
+}
+
+/// Brightness in `0..=1` between the `lines` field lines of an escaping
+/// point, `0` on the lines.
+///
+/// The final point $z_n$ lies a fraction $\nu$ of the way from the inner edge
+/// to the outer edge of its escape band, where $z_{n-1}$ reached the escape
+/// radius. Lines are drawn through the angles of $z_n$ that are multiples of
+/// $2\pi / (d \cdot lines)$ at the inner edge, blended towards multiples of
+/// $2\pi / lines$ at the outer one. Since $z_{n+1} = z_n^d + c$, the outer
+/// edge of band $n + 1$ has the same lines as the inner edge of band $n$.
+fn field_line_shade(lines: u32, result: &EscapeResult, context: &ColorContext) -> f64 {
+    let angle = result.z.arg();
+    let smooth = smooth_count(
+        result.count,
+        result.z,
+        context.escape_radius,
+        context.degree,
+    );
+    let nu = (result.count as f64 - smooth).clamp(0., 1.);
+    let shade = |lines: f64| (lines * angle / 2.).sin().abs();
+    let inner = (context.degree * lines as f64).round();
+    (1. - nu) * shade(inner) + nu * shade(lines as f64)
+}
+
+/// Number of consecutive periods spread over one run through the palette by
+/// [`InteriorColoring::Period`].
+pub const PERIOD_COLORS: u32 = 12;
+
+/// Interior distance, in pixels, that [`InteriorColoring::Distance`] maps to
+/// the middle of the palette.
+pub const INTERIOR_DISTANCE_SCALE: f64 = 16.;
+
+/// How points that never escaped are colored.
+///
+/// Apart from the final angle, the colorings rely on the
+/// [attracting cycle](crate::escape::attracting_cycle) the orbit converged
+/// to; points whose cycle isn't found, e.g. close to the boundary or for
+/// fractals without partial derivatives, stay `BLACK`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum InteriorColoring {
+    /// Everything `BLACK`.
+    #[default]
+    Black,
+    /// Position growing with the interior distance estimate, from `0` at the
+    /// boundary of the hyperbolic component towards `1` deep inside.
+    Distance,
+    /// Position given by the period of the cycle, so that every hyperbolic
+    /// component gets a uniform color.
+    Period,
+    /// Position given by the absolute value of the cycle's multiplier, `0` at
+    /// the centers of the hyperbolic components and `1` at their boundaries.
+    Multiplier,
+    /// Position given by the angle of the cycle's multiplier.
+    MultiplierAngle,
+    /// Position given by the angle of the last point of the orbit.
+    FinalAngle,
+}
+
+impl InteriorColoring {
+    /// Quantities the coloring needs tracked along the orbits.
+    pub fn track(&self) -> Track {
+        Track {
+            cycle: !matches!(self, InteriorColoring::Black | InteriorColoring::FinalAngle),
+            final_z: *self == InteriorColoring::FinalAngle,
+            ..Track::default()
+        }
+    }
+
+    /// Palette position in `0..=1` of a bounded point with the given
+    /// `result`, or `None` if it is painted `BLACK`.
+    pub fn position(&self, result: &EscapeResult, context: &ColorContext) -> Option<f64> {
+        let angle = |z: ComplexDouble| (z.arg() / (2. * PI)).rem_euclid(1.);
+        let cycle = result.cycle;
+
+        match self {
+            InteriorColoring::Black => None,
+            InteriorColoring::Distance => {
+                let distance = cycle?.interior_distance? / context.pixel_size;
+                Some(distance / (distance + INTERIOR_DISTANCE_SCALE))
+            }
+            InteriorColoring::Period => {
+                Some(((cycle?.period - 1) % PERIOD_COLORS) as f64 / PERIOD_COLORS as f64)
+            }
+            InteriorColoring::Multiplier => Some(cycle?.multiplier.norm().min(1.)),
+            InteriorColoring::MultiplierAngle => Some(angle(cycle?.multiplier)),
+            InteriorColoring::FinalAngle => Some(angle(result.z)),
+        }
+    }
+
+    /// Color of a bounded point, see [`InteriorColoring::position`].
+    pub fn color(
+        &self,
+        palette: &Palette,
+        result: &EscapeResult,
+        context: &ColorContext,
+    ) -> RGBColor {
+        self.position(result, context)
+            .map_or(BLACK, |t| palette.color(t))
+    }
+}
+
+/// What besides its own [`EscapeResult`] determines the color of a point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorContext {
+    /// Maximum number of iterations per point.
+    pub num_iterations: u32,
+    /// Escape radius the orbits were iterated with.
+    pub escape_radius: f64,
+    /// See [`Fractal::degree`](crate::fractal::Fractal::degree).
+    pub degree: f64,
+    /// Distance between neighbouring pixels in the complex plane.
+    pub pixel_size: f64,
+    /// Distribution of the escape values over the render, for
+    /// [`Coloring::Histogram`].
+    pub histogram: Option<Histogram>,
+}
+
+/// Distribution of a set of escape values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// The values in ascending order.
+    values: Vec<f64>,
+}
+
+impl Histogram {
+    pub fn new(mut values: Vec<f64>) -> Self {
+        values.sort_by(f64::total_cmp);
+        Histogram { values }
+    }
+
+    /// Share of the values below `value`, values equal to it counting half.
+    pub fn rank(&self, value: f64) -> f64 {
+        if self.values.is_empty() {
+            return 0.;
+        }
+        let below = self.values.partition_point(|&x| x < value);
+        let up_to = self.values.partition_point(|&x| x <= value);
+        (below + up_to) as f64 / (2 * self.values.len()) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::average::OrbitAverage;
+    use crate::escape::{Cycle, Status};
+
+    fn escaped(count: u32, z: ComplexDouble) -> EscapeResult {
+        EscapeResult {
+            status: Status::Escaped,
+            count,
+            z,
+            ..EscapeResult::default()
+        }
+    }
+
+    fn context(escape_radius: f64) -> ColorContext {
+        ColorContext {
+            num_iterations: 100,
+            escape_radius,
+            degree: 2.,
+            pixel_size: 0.01,
+            histogram: None,
+        }
+    }
+
+    #[test]
+    fn escape_color_test() {
+        let coloring = Coloring::EscapeTime;
+        let bounded = EscapeResult {
+            count: 100,
+            ..EscapeResult::default()
+        };
+        let z = ComplexDouble::new(3., 0.);
+        let palette = Palette::default();
+        let context = context(2.);
+
+        assert_eq!(coloring.color(&palette, &bounded, &context), BLACK);
+        assert_eq!(
+            coloring.color(&palette, &escaped(0, z), &context),
+            RGBColor(255, 0, 0)
+        );
+        //  Escaping in the very last iteration isn't mistaken for the interior
+        assert_ne!(coloring.color(&palette, &escaped(100, z), &context), BLACK);
+    }
+
+    #[test]
+    fn smooth_position_test() {
+        let coloring = Coloring::Smooth;
+        let radius = coloring.min_escape_radius();
+        let context = context(radius);
+
+        //  Just outside the escape radius the position matches the count
+        let result = escaped(10, ComplexDouble::new(radius + 1e-9, 0.));
+        let t = coloring.position(&result, &context);
+        assert!((t.unwrap() - 0.1).abs() < 1e-9);
+
+        //  Squaring the escape radius costs exactly one iteration
+        let result = escaped(10, ComplexDouble::new(0., radius * radius));
+        let t = coloring.position(&result, &context);
+        assert!((t.unwrap() - 0.09).abs() < 1e-9);
+
+        assert_eq!(coloring.position(&EscapeResult::default(), &context), None);
+    }
+
+    #[test]
+    fn histogram_test() {
+        let histogram = Histogram::new(vec![3., 1., 2., 2.]);
+        assert_eq!(histogram.rank(0.), 0.);
+        assert_eq!(histogram.rank(1.5), 0.25);
+        assert_eq!(histogram.rank(2.), 0.5);
+        assert_eq!(histogram.rank(5.), 1.);
+        assert_eq!(Histogram::new(vec![]).rank(1.), 0.);
+
+        //  Counts crowded at the low end are spread over the whole palette
+        let coloring = Coloring::Histogram;
+        let radius = coloring.min_escape_radius();
+        let results: Vec<_> = (0..100)
+            .map(|i| escaped(10 + i / 25, ComplexDouble::new(radius + 1e-9, 0.)))
+            .collect();
+        let mut context = ColorContext {
+            num_iterations: 1000,
+            ..context(radius)
+        };
+        context.histogram = coloring.histogram(&results, &context);
+        let positions: Vec<_> = [0, 25, 50, 75]
+            .map(|i| coloring.position(&results[i], &context).unwrap())
+            .to_vec();
+        assert_eq!(positions, vec![0.125, 0.375, 0.625, 0.875]);
+    }
+
+    #[test]
+    fn distance_test() {
+        let coloring = Coloring::Distance { thickness: 2. };
+        let palette = Palette::default();
+        let context = context(coloring.min_escape_radius());
+        let far = EscapeResult {
+            derivative: Some(ComplexDouble::new(1., 0.)),
+            ..escaped(3, ComplexDouble::new(300., 0.))
+        };
+        let distance = far.exterior_distance().unwrap();
+        let smooth = Coloring::Smooth.color(&palette, &far, &context);
+
+        //  Points further out than the thickness keep the smooth color
+        assert_eq!(coloring.color(&palette, &far, &context), smooth);
+
+        let near = EscapeResult {
+            derivative: Some(ComplexDouble::new(distance / context.pixel_size, 0.)),
+            ..far
+        };
+        //  One pixel away with a thickness of two pixels is half as bright
+        let RGBColor(r, _, _) = coloring.color(&palette, &near, &context);
+        assert_eq!(r, (smooth.0 as f64 / 2.).round() as u8);
+
+        let no_derivative = EscapeResult {
+            derivative: None,
+            ..far
+        };
+        assert_eq!(coloring.color(&palette, &no_derivative, &context), smooth);
+
+        assert!(coloring.validate().is_ok());
+        assert!(Coloring::Distance { thickness: 0. }.validate().is_err());
+    }
+
+    #[test]
+    fn average_test() {
+        let coloring = Coloring::StripeAverage { density: 1. };
+        let context = context(coloring.min_escape_radius());
+        let statistic = coloring.statistic().unwrap();
+        let c = ComplexDouble::new(0., 0.);
+        let average = [1., 2.]
+            .map(|angle: f64| ComplexDouble::from_polar(2., angle))
+            .iter()
+            .fold(OrbitAverage::default(), |average, &z| {
+                average.update(&statistic, z, c)
+            });
+        let result = |z| EscapeResult {
+            average: Some(average),
+            ..escaped(2, z)
+        };
+
+        //  Just past the escape radius the last term counts fully, and not at
+        //  all once the orbit went as far as the next step would take it
+        let radius = context.escape_radius;
+        let near = coloring.position(&result(ComplexDouble::new(radius, 0.)), &context);
+        assert!((near.unwrap() - average.interpolate(1.).unwrap()).abs() < 1e-12);
+        let far = coloring.position(&result(ComplexDouble::new(radius * radius, 0.)), &context);
+        assert!((far.unwrap() - average.interpolate(0.).unwrap()).abs() < 1e-12);
+
+        assert_eq!(coloring.position(&escaped(2, c), &context), None);
+        assert_eq!(
+            Coloring::TriangleAverage.track().average,
+            Some(Statistic::TriangleInequality)
+        );
+        assert!(Coloring::StripeAverage { density: f64::NAN }
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn decomposition_test() {
+        let palette = Palette::default();
+        let context = context(Coloring::BinaryDecomposition.min_escape_radius());
+        let above = escaped(20, ComplexDouble::new(300., 1.));
+        let below = escaped(20, ComplexDouble::new(300., -1.));
+        let coloring = Coloring::BinaryDecomposition;
+        assert_eq!(
+            coloring.color(&palette, &above, &context),
+            Coloring::EscapeTime.color(&palette, &above, &context)
+        );
+        assert_eq!(coloring.color(&palette, &below, &context), BLACK);
+
+        let angle = Coloring::ExternalAngle;
+        assert_eq!(
+            angle.position(&escaped(3, ComplexDouble::new(0., 300.)), &context),
+            Some(0.25)
+        );
+        assert_eq!(
+            angle.position(&escaped(3, ComplexDouble::new(0., -300.)), &context),
+            Some(0.75)
+        );
+    }
+
+    #[test]
+    fn field_lines_test() {
+        let context = context(SMOOTH_ESCAPE_RADIUS);
+        let radius = context.escape_radius;
+        let shade = |count, z| field_line_shade(4, &escaped(count, z), &context);
+
+        //  Dark on the lines at the outer edge of the band, bright between
+        let outer = |angle: f64| ComplexDouble::from_polar(radius * radius, angle);
+        assert!(shade(5, outer(PI / 2.)) < 1e-12);
+        assert!((shade(5, outer(PI / 4.)) - 1.).abs() < 1e-12);
+
+        //  Continuous across the edge between two bands, z₆ being z₅²
+        for angle in [0.1, 1., 2.5, -3.] {
+            let inner = ComplexDouble::from_polar(radius * (1. + 1e-12), angle);
+            let next = ComplexDouble::from_polar(radius * radius * (1. + 2e-12), 2. * angle);
+            assert!((shade(5, inner) - shade(6, next)).abs() < 1e-6);
+        }
+
+        assert!(Coloring::FieldLines { lines: 0 }.validate().is_err());
+    }
+
+    #[test]
+    fn interior_test() {
+        let context = context(2.);
+        let result = EscapeResult {
+            z: ComplexDouble::new(0., -1.),
+            cycle: Some(Cycle {
+                period: 14,
+                multiplier: ComplexDouble::new(-0.5, 0.),
+                interior_distance: Some(16. * context.pixel_size),
+            }),
+            ..EscapeResult::default()
+        };
+        let position = |interior: InteriorColoring| interior.position(&result, &context);
+
+        assert_eq!(position(InteriorColoring::Black), None);
+        assert_eq!(position(InteriorColoring::Distance), Some(0.5));
+        assert_eq!(position(InteriorColoring::Period), Some(1. / 12.));
+        assert_eq!(position(InteriorColoring::Multiplier), Some(0.5));
+        assert_eq!(position(InteriorColoring::MultiplierAngle), Some(0.5));
+        assert_eq!(position(InteriorColoring::FinalAngle), Some(0.75));
+
+        let no_cycle = EscapeResult::default();
+        assert_eq!(InteriorColoring::Period.position(&no_cycle, &context), None);
+        assert!(InteriorColoring::FinalAngle
+            .position(&no_cycle, &context)
+            .is_some());
+    }
+}
@@ -0,0 +1,419 @@
+//! Double-double numbers, with about 106 bits of mantissa.
+//!
+//! A [`DoubleDouble`] is the unevaluated sum of two `f64`, the second one
+//! holding the rounding error of the first. Arithmetic uses error-free
+//! transformations of `f64` operations, so it is exact up to the last bits
+//! of the combined mantissa and runs at a fixed cost, unlike
+//! [`Fixed`](crate::bignum::Fixed) numbers. Points that are still
+//! distinguishable can be zoomed in on to about $10^{-28}$.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::num::ParseFloatError;
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+use std::str::FromStr;
+
+use num::complex::Complex;
+use num::{Num, One, Zero};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Complex number type with double-double parts.
+pub type ComplexDoubleDouble = Complex<DoubleDouble>;
+
+/// Significant decimal digits read and written, about as many as the
+/// mantissa holds.
+const DIGITS: usize = 32;
+
+/// Number `hi + lo` with `|lo|` at most half an ulp of `hi`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DoubleDouble {
+    pub hi: f64,
+    pub lo: f64,
+}
+
+/// `a + b` and its rounding error.
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let bb = s - a;
+    (s, (a - (s - bb)) + (b - bb))
+}
+
+/// Same as [`two_sum`] when `|a| >= |b|`.
+fn quick_two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    (s, b - (s - a))
+}
+
+/// `a * b` and its rounding error.
+fn two_prod(a: f64, b: f64) -> (f64, f64) {
+    let p = a * b;
+    (p, a.mul_add(b, -p))
+}
+
+impl DoubleDouble {
+    pub fn new(x: f64) -> Self {
+        DoubleDouble { hi: x, lo: 0. }
+    }
+
+    /// Closest `f64`.
+    pub fn to_f64(self) -> f64 {
+        self.hi + self.lo
+    }
+
+    pub fn abs(self) -> Self {
+        match self.hi < 0. {
+            true => -self,
+            false => self,
+        }
+    }
+
+    pub fn is_finite(self) -> bool {
+        self.hi.is_finite() && self.lo.is_finite()
+    }
+
+    fn normalized(hi: f64, lo: f64) -> Self {
+        let (hi, lo) = quick_two_sum(hi, lo);
+        DoubleDouble { hi, lo }
+    }
+
+    /// `10^exponent`.
+    fn power_of_ten(exponent: i32) -> Self {
+        let (mut power, mut square) = (DoubleDouble::one(), DoubleDouble::new(10.));
+        let mut bits = exponent.unsigned_abs();
+        while bits > 0 {
+            if bits & 1 == 1 {
+                power = power * square;
+            }
+            square = square * square;
+            bits >>= 1;
+        }
+        match exponent < 0 {
+            true => DoubleDouble::one() / power,
+            false => power,
+        }
+    }
+}
+
+impl From<f64> for DoubleDouble {
+    fn from(x: f64) -> Self {
+        DoubleDouble::new(x)
+    }
+}
+
+impl fmt::Display for DoubleDouble {
+    /// The shortest `f64` form when the number is an `f64`, otherwise its
+    /// first 32 significant digits in scientific notation.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.lo == 0. || !self.is_finite() {
+            return fmt::Display::fmt(&self.hi, f);
+        }
+
+        let mut exponent = self.hi.abs().log10().floor() as i32;
+        let mut x = self.abs() * DoubleDouble::power_of_ten(-exponent);
+        if x.hi >= 10. {
+            x = x / DoubleDouble::new(10.);
+            exponent += 1;
+        } else if x.hi < 1. {
+            x = x * DoubleDouble::new(10.);
+            exponent -= 1;
+        }
+        //  One more digit than written, to round the last one
+        let mut digits = Vec::with_capacity(DIGITS + 1);
+        for _ in 0..=DIGITS {
+            let mut digit = x.hi.floor();
+            if (x - DoubleDouble::new(digit)).hi < 0. {
+                digit -= 1.;
+            }
+            let digit = digit.clamp(0., 9.);
+            digits.push(digit as u8);
+            x = (x - DoubleDouble::new(digit)) * DoubleDouble::new(10.);
+        }
+        let mut carry = digits.pop() >= Some(5);
+        for digit in digits.iter_mut().rev() {
+            *digit += u8::from(carry);
+            carry = *digit == 10;
+            if carry {
+                *digit = 0;
+            }
+        }
+        if carry {
+            digits.insert(0, 1);
+            digits.pop();
+            exponent += 1;
+        }
+
+        let digits: String = digits
+            .iter()
+            .map(|digit| char::from(b'0' + digit))
+            .collect();
+        let digits = digits.trim_end_matches('0');
+        let sign = if self.hi < 0. { "-" } else { "" };
+        match digits.len() {
+            1 => write!(f, "{}{}e{}", sign, digits, exponent),
+            _ => write!(f, "{}{}.{}e{}", sign, &digits[..1], &digits[1..], exponent),
+        }
+    }
+}
+
+impl FromStr for DoubleDouble {
+    type Err = ParseFloatError;
+
+    /// Parses the same syntax as `f64`, keeping the first 32 significant
+    /// digits of decimal numbers.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let approximation: f64 = s.parse()?;
+        let (mantissa, exponent) = s.split_once(['e', 'E']).unwrap_or((s, "0"));
+        let Ok(mut exponent) = exponent.parse::<i32>() else {
+            return Ok(DoubleDouble::new(approximation));
+        };
+        if approximation == 0. || !approximation.is_finite() {
+            return Ok(DoubleDouble::new(approximation));
+        }
+
+        let (mut value, mut significant, mut fraction) = (DoubleDouble::zero(), 0, false);
+        for c in mantissa.chars() {
+            let Some(digit) = c.to_digit(10) else {
+                fraction |= c == '.';
+                continue;
+            };
+            if significant < DIGITS {
+                value = value * DoubleDouble::new(10.) + DoubleDouble::new(digit as f64);
+                significant += usize::from(!value.is_zero());
+                exponent -= i32::from(fraction);
+            } else if !fraction {
+                exponent += 1;
+            }
+        }
+        let x = match exponent < 0 {
+            true => value / DoubleDouble::power_of_ten(-exponent),
+            false => value * DoubleDouble::power_of_ten(exponent),
+        };
+
+        //  Beyond the range of normal numbers, f64 parsing is as good
+        if !(x.hi.is_normal() && x.is_finite()) {
+            return Ok(DoubleDouble::new(approximation));
+        }
+        Ok(match approximation < 0. {
+            true => -x,
+            false => x,
+        })
+    }
+}
+
+impl Serialize for DoubleDouble {
+    /// An `f64` stays a number, other values are written as strings holding
+    /// all their digits.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.lo {
+            0. => serializer.serialize_f64(self.hi),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DoubleDouble {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DoubleDoubleVisitor;
+
+        impl Visitor<'_> for DoubleDoubleVisitor {
+            type Value = DoubleDouble;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a number or a string holding one")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<DoubleDouble, E> {
+                s.parse().map_err(E::custom)
+            }
+
+            fn visit_f64<E: de::Error>(self, x: f64) -> Result<DoubleDouble, E> {
+                Ok(DoubleDouble::new(x))
+            }
+
+            fn visit_i64<E: de::Error>(self, x: i64) -> Result<DoubleDouble, E> {
+                Ok(x.to_string().parse().expect("integers are numbers"))
+            }
+
+            fn visit_u64<E: de::Error>(self, x: u64) -> Result<DoubleDouble, E> {
+                Ok(x.to_string().parse().expect("integers are numbers"))
+            }
+        }
+
+        deserializer.deserialize_any(DoubleDoubleVisitor)
+    }
+}
+
+impl PartialOrd for DoubleDouble {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.hi.partial_cmp(&other.hi)? {
+            Ordering::Equal => self.lo.partial_cmp(&other.lo),
+            ordering => Some(ordering),
+        }
+    }
+}
+
+impl Neg for DoubleDouble {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        DoubleDouble {
+            hi: -self.hi,
+            lo: -self.lo,
+        }
+    }
+}
+
+impl Add for DoubleDouble {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let (s, e) = two_sum(self.hi, other.hi);
+        let (t, f) = two_sum(self.lo, other.lo);
+        let (s, e) = quick_two_sum(s, e + t);
+        DoubleDouble::normalized(s, e + f)
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let (p, e) = two_prod(self.hi, other.hi);
+        DoubleDouble::normalized(p, e + (self.hi * other.lo + self.lo * other.hi))
+    }
+}
+
+impl Div for DoubleDouble {
+    type Output = Self;
+
+    /// Long division by the leading part of `other`, three digits deep.
+    fn div(self, other: Self) -> Self {
+        let q1 = self.hi / other.hi;
+        let r = self - other * DoubleDouble::new(q1);
+        let q2 = r.hi / other.hi;
+        let r = r - other * DoubleDouble::new(q2);
+        let q3 = r.hi / other.hi;
+        DoubleDouble::normalized(q1, q2) + DoubleDouble::new(q3)
+    }
+}
+
+impl Rem for DoubleDouble {
+    type Output = Self;
+
+    /// Remainder of the division truncated towards zero, like `f64`.
+    fn rem(self, other: Self) -> Self {
+        let quotient = self / other;
+        let truncated = match quotient.hi.fract() {
+            0. => DoubleDouble::normalized(quotient.hi, quotient.lo.trunc()),
+            _ => DoubleDouble::new(quotient.hi.trunc()),
+        };
+        self - other * truncated
+    }
+}
+
+impl Mul<DoubleDouble> for f64 {
+    type Output = DoubleDouble;
+
+    fn mul(self, other: DoubleDouble) -> DoubleDouble {
+        DoubleDouble::new(self) * other
+    }
+}
+
+impl Zero for DoubleDouble {
+    fn zero() -> Self {
+        DoubleDouble::new(0.)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.hi == 0.
+    }
+}
+
+impl One for DoubleDouble {
+    fn one() -> Self {
+        DoubleDouble::new(1.)
+    }
+}
+
+impl Num for DoubleDouble {
+    type FromStrRadixErr = num::traits::ParseFloatError;
+
+    /// Same as [`DoubleDouble::from_str`] in radix `10`, other radixes
+    /// keep only `f64` precision.
+    fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+        let approximation = f64::from_str_radix(s, radix)?;
+        Ok(match radix {
+            10 => s.parse().unwrap_or(DoubleDouble::new(approximation)),
+            _ => DoubleDouble::new(approximation),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_test() {
+        let one = DoubleDouble::new(1.);
+        let tiny = DoubleDouble::new(1e-20);
+        assert_eq!((one + tiny) - one, tiny);
+        assert_eq!(((one + tiny) * (one + tiny) - one).to_f64(), 2e-20);
+        assert!(one + tiny > one);
+
+        let third = one / DoubleDouble::new(3.);
+        assert_eq!(third.hi, 1. / 3.);
+        assert!((third * DoubleDouble::new(3.) - one).abs().to_f64() < 1e-31);
+        assert_eq!(
+            DoubleDouble::new(7.5) % DoubleDouble::new(2.),
+            DoubleDouble::new(1.5)
+        );
+        assert_eq!((-third).abs(), third);
+    }
+
+    #[test]
+    fn complex_test() {
+        let z = ComplexDoubleDouble::new(DoubleDouble::new(1.), DoubleDouble::new(1e-20));
+        let square = z * z;
+        assert_eq!(square.re.to_f64(), 1.);
+        assert_eq!(square.im.to_f64(), 2e-20);
+        assert_eq!((square - z * z).norm_sqr(), DoubleDouble::zero());
+    }
+
+    #[test]
+    fn parse_test() {
+        let parse = |s: &str| s.parse::<DoubleDouble>().unwrap();
+        let one = DoubleDouble::new(1.);
+        assert_eq!(parse("-1.25"), DoubleDouble::new(-1.25));
+        let tiny = parse("1.00000000000000000001") - one;
+        assert!((tiny - parse("1e-20")).abs().to_f64() < 1e-31);
+        assert!((parse("0.1") * DoubleDouble::new(10.) - one).abs().to_f64() < 1e-32);
+        let third = parse("0.3333333333333333333333333333333333");
+        assert!((third * DoubleDouble::new(3.) - one).abs().to_f64() < 1e-32);
+        assert_eq!(parse("1e400").hi, f64::INFINITY);
+        assert!("1,5".parse::<DoubleDouble>().is_err());
+    }
+
+    #[test]
+    fn display_test() {
+        assert_eq!(DoubleDouble::new(-0.745).to_string(), "-0.745");
+        let x = DoubleDouble::new(1.) + DoubleDouble::new(1e-20);
+        assert_eq!(x.to_string(), "1.00000000000000000001e0");
+        let third = DoubleDouble::new(-1.) / DoubleDouble::new(3.);
+        assert!(
+            (third.to_string().parse::<DoubleDouble>().unwrap() - third)
+                .abs()
+                .to_f64()
+                < 1e-32
+        );
+    }
+}
@@ -0,0 +1,817 @@
+//! Escape-time iteration of the Mandelbrot recurrence.
+
+use num::complex::Complex;
+use num::Zero;
+
+use crate::average::{OrbitAverage, Statistic};
+use crate::fractal::{Fractal, Mandelbrot};
+use crate::precision::Real;
+use crate::trap::{TrapHit, TrapShape};
+
+/// Complex number type used for points of the complex plane.
+pub type ComplexDouble = Complex<f64>;
+
+/// Radius of the smallest circle whose outside is known to diverge.
+pub const DEFAULT_ESCAPE_RADIUS: f64 = 2.;
+
+/// Escape radius large enough for [`smooth_count`] to hide the escape bands.
+pub const SMOOTH_ESCAPE_RADIUS: f64 = 256.;
+
+/// Method implementing the mandelbrot condition
+/// $$f_c(z) = z^2 + c$$
+///
+/// Returns the number of iterations after which the orbit of `0` left the
+/// circle of radius 2, or `num_iterations` if it never did. Use [`escape`]
+/// to tell the two apart when the orbit escapes in the last iteration.
+///
+/// * `c`: Complex number input (e.g. pixel coordinate in mandelbrot image),
+///   in any [`Real`] precision
+/// * `num_iterations`: Number of iterations to perform
+pub fn mandelbrot<T: Real>(c: &Complex<T>, num_iterations: u32) -> u32 {
+    mandelbrot_with(c, num_iterations, DEFAULT_ESCAPE_RADIUS, Track::default()).count
+}
+
+/// Same as [`mandelbrot`] for any `fractal`, where the orbit escapes once it
+/// leaves the circle of radius `escape_radius`.
+///
+/// Radii larger than [`DEFAULT_ESCAPE_RADIUS`] give the same set but spread
+/// the escape counts of the outside further apart.
+pub fn escape_count<F: Fractal + ?Sized>(
+    fractal: &F,
+    c: &ComplexDouble,
+    num_iterations: u32,
+    escape_radius: f64,
+) -> u32 {
+    escape(fractal, c, num_iterations, escape_radius).count
+}
+
+/// Whether an orbit left the escape radius.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// The orbit left the escape radius.
+    Escaped,
+    /// The orbit stayed inside the escape radius for all iterations.
+    Bounded,
+    /// The orbit was found to repeat itself, or the fractal knows the point
+    /// to be bounded; unlike [`Status::Bounded`] it is certain never to escape.
+    Periodic,
+}
+
+/// Optional quantities [`escape_with`] keeps track of along the orbit.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Track {
+    /// Derivative of the orbit with respect to the pixel.
+    pub derivative: bool,
+    /// Smallest distance of the orbit from the origin.
+    pub min_distance: bool,
+    /// Attracting cycle bounded orbits converge to.
+    pub cycle: bool,
+    /// Exact last point of bounded orbits, which rules out the early-outs.
+    pub final_z: bool,
+    /// Closest point of the orbit to an orbit trap.
+    pub trap: Option<TrapShape>,
+    /// Sum of a statistic over the orbit.
+    pub average: Option<Statistic>,
+}
+
+/// Distance below which an orbit point counts as a repetition of an earlier
+/// one in the periodicity check.
+const PERIODICITY_TOLERANCE: f64 = 1e-13;
+
+/// Same as [`PERIODICITY_TOLERANCE`] in units of the last place of the
+/// mantissa, for other precisions.
+const PERIODICITY_TOLERANCE_ULPS: f64 = 1024.;
+
+/// Periodicity tolerance of [`mandelbrot_with`] in the precision `T`.
+pub(crate) fn periodicity_tolerance<T: Real>() -> T {
+    T::from_f64(PERIODICITY_TOLERANCE_ULPS * 0.5f64.powi(T::MANTISSA_BITS as i32))
+}
+
+/// Longest period [`attracting_cycle`] looks for.
+pub const MAX_PERIOD: u32 = 256;
+
+/// Attracting cycle of the iteration for a fixed pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cycle {
+    /// Number of points on the cycle.
+    pub period: u32,
+    /// Derivative of `period` steps at a point of the cycle, with an absolute
+    /// value below `1`.
+    pub multiplier: ComplexDouble,
+    /// Estimate of the distance from the pixel to the boundary of the
+    /// hyperbolic component it lies in, if moving the pixel moves the cycle.
+    /// The true distance lies between a quarter of the estimate and the
+    /// estimate.
+    pub interior_distance: Option<f64>,
+}
+
+/// Relative distance below which an orbit point counts as having come back.
+const CYCLE_TOLERANCE: f64 = 1e-3;
+/// Relative Newton step below which a cycle point counts as found.
+const NEWTON_TOLERANCE: f64 = 1e-12;
+const NEWTON_STEPS: u32 = 64;
+
+/// Attracting cycle of `fractal` for the pixel `c` near the orbit point `z`,
+/// of a period up to `max_period`, if there is one and the fractal has
+/// [`Fractal::partials`].
+///
+/// Periods that bring the orbit back close to `z` are refined into an exact
+/// cycle point by Newton's method; the shortest one that turns out to be
+/// attracting is returned.
+pub fn attracting_cycle<F: Fractal + ?Sized>(
+    fractal: &F,
+    c: ComplexDouble,
+    z: ComplexDouble,
+    max_period: u32,
+) -> Option<Cycle> {
+    fractal.partials(z, c)?;
+
+    let mut w = z;
+    for period in 1..=max_period {
+        w = fractal.step(w, c);
+        if (w - z).norm() >= CYCLE_TOLERANCE * (1. + z.norm()) {
+            continue;
+        }
+        if let Some(cycle) = refine_cycle(fractal, c, z, period) {
+            return Some(cycle);
+        }
+    }
+    None
+}
+
+/// Cycle of length `period` near `z`, if Newton's method finds one and it is
+/// attracting.
+fn refine_cycle<F: Fractal + ?Sized>(
+    fractal: &F,
+    c: ComplexDouble,
+    mut z: ComplexDouble,
+    period: u32,
+) -> Option<Cycle> {
+    let mut converged = false;
+    for _ in 0..NEWTON_STEPS {
+        let (mut w, mut dw) = (z, ComplexDouble::new(1., 0.));
+        for _ in 0..period {
+            dw *= fractal.partials(w, c)?.dz;
+            w = fractal.step(w, c);
+        }
+        let delta = (w - z) / (dw - 1.);
+        z -= delta;
+        if !(delta.norm().is_finite() && z.norm().is_finite()) {
+            return None;
+        }
+        if delta.norm() <= NEWTON_TOLERANCE * (1. + z.norm()) {
+            converged = true;
+            break;
+        }
+    }
+    if !converged {
+        return None;
+    }
+
+    //  Derivatives of `period` steps, with respect to z and the pixel
+    let zero = ComplexDouble::new(0., 0.);
+    let (mut dz, mut dzdz, mut dc, mut dcdz) = (ComplexDouble::new(1., 0.), zero, zero, zero);
+    let mut w = z;
+    for _ in 0..period {
+        let p = fractal.partials(w, c)?;
+        dcdz = (p.dzdz * dc + p.dcdz) * dz + p.dz * dcdz;
+        dzdz = p.dzdz * dz * dz + p.dz * dzdz;
+        dc = p.dz * dc + p.dc;
+        dz *= p.dz;
+        w = fractal.step(w, c);
+    }
+    if dz.norm() >= 1. || dz.norm().is_nan() {
+        return None;
+    }
+
+    let distance = (1. - dz.norm_sqr()) / (dcdz + dzdz * dc / (1. - dz)).norm();
+    Some(Cycle {
+        period,
+        multiplier: dz,
+        interior_distance: Some(distance).filter(|distance| distance.is_finite()),
+    })
+}
+
+/// Outcome of iterating a single point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EscapeResult {
+    pub status: Status,
+    /// Number of iterations after which the orbit escaped, or the number of
+    /// iterations performed if it stayed bounded.
+    pub count: u32,
+    /// Last point of the orbit, the first one outside the escape radius for
+    /// escaping orbits.
+    pub z: ComplexDouble,
+    /// Derivative of `z` with respect to the pixel, if it was tracked and the
+    /// fractal has one, see [`Fractal::derivative`].
+    pub derivative: Option<ComplexDouble>,
+    /// Smallest `|z|` over the orbit without its initial point, if tracked.
+    /// Infinite if the initial point already escaped.
+    pub min_distance: Option<f64>,
+    /// Attracting cycle of a bounded orbit, if tracked and found, see
+    /// [`attracting_cycle`].
+    pub cycle: Option<Cycle>,
+    /// Closest point of the orbit without its initial point to the trap, if
+    /// tracked.
+    pub trap: Option<TrapHit>,
+    /// Sum of the statistic over the orbit without its initial point, if
+    /// tracked.
+    pub average: Option<OrbitAverage>,
+}
+
+impl EscapeResult {
+    pub fn escaped(&self) -> bool {
+        self.status == Status::Escaped
+    }
+
+    /// Estimate $2|z|\ln|z| / |dz|$ of the distance from the pixel to the
+    /// set, if the orbit escaped and its derivative was tracked.
+    ///
+    /// For the Mandelbrot set the true distance lies between a quarter of the
+    /// estimate and the estimate. The estimate gets better with larger escape
+    /// radii.
+    pub fn exterior_distance(&self) -> Option<f64> {
+        let dz = self.derivative.filter(|_| self.escaped())?;
+        let r = self.z.norm();
+        Some(2. * r * r.ln() / dz.norm())
+    }
+}
+
+impl Default for EscapeResult {
+    fn default() -> Self {
+        EscapeResult {
+            status: Status::Bounded,
+            count: 0,
+            z: ComplexDouble::new(0., 0.),
+            derivative: None,
+            min_distance: None,
+            cycle: None,
+            trap: None,
+            average: None,
+        }
+    }
+}
+
+/// Same as [`escape_count`], but returns the whole [`EscapeResult`], without
+/// any of the optional quantities.
+pub fn escape<F: Fractal + ?Sized>(
+    fractal: &F,
+    c: &ComplexDouble,
+    num_iterations: u32,
+    escape_radius: f64,
+) -> EscapeResult {
+    escape_with(fractal, c, num_iterations, escape_radius, Track::default())
+}
+
+/// Same as [`escape`], also computing the quantities selected by `track`.
+///
+/// Bounded orbits are cut short in two ways, unless `track.final_z` is set:
+/// points the fractal knows to be bounded, see [`Fractal::certainly_bounded`],
+/// aren't iterated at all unless the orbit itself is tracked, and orbits
+/// that come back to an earlier point, as found by Brent's cycle detection,
+/// stop there. Either way the status is [`Status::Periodic`] and the count is
+/// that of the full iteration, while `z` and the derivative are where the
+/// orbit stopped.
+pub fn escape_with<F: Fractal + ?Sized>(
+    fractal: &F,
+    c: &ComplexDouble,
+    num_iterations: u32,
+    escape_radius: f64,
+    track: Track,
+) -> EscapeResult {
+    let orbit = FractalOrbit {
+        fractal,
+        c: *c,
+        escape_radius,
+    };
+    iterate(&orbit, num_iterations, track)
+}
+
+/// Same as [`escape_with`] for the Mandelbrot set, iterated with the
+/// precision of `c`.
+///
+/// The early-outs are the same, with a periodicity tolerance that scales
+/// with the precision. The cycle of bounded orbits is looked for in `f64`.
+pub fn mandelbrot_with<T: Real>(
+    c: &Complex<T>,
+    num_iterations: u32,
+    escape_radius: f64,
+    track: Track,
+) -> EscapeResult {
+    let orbit = MandelbrotOrbit {
+        c: *c,
+        radius_sqr: T::from_f64(escape_radius * escape_radius),
+    };
+    iterate(&orbit, num_iterations, track)
+}
+
+/// Recurrence of a single pixel, iterated by [`iterate`] in the precision
+/// `Self::Real`.
+trait Orbit {
+    type Real: Real;
+
+    fn initial(&self) -> Complex<Self::Real>;
+    fn initial_derivative(&self) -> Complex<Self::Real>;
+    fn escaped(&self, z: Complex<Self::Real>) -> bool;
+    fn step(&self, z: Complex<Self::Real>) -> Complex<Self::Real>;
+    fn derivative(
+        &self,
+        z: Complex<Self::Real>,
+        dz: Complex<Self::Real>,
+    ) -> Option<Complex<Self::Real>>;
+    /// See [`Fractal::certainly_bounded`].
+    fn certainly_bounded(&self) -> bool;
+    /// Constant of the recurrence, see [`Fractal::constant`].
+    fn constant(&self) -> ComplexDouble;
+    /// Distance below which an orbit point repeats an earlier one.
+    fn periodicity_tolerance(&self) -> Self::Real;
+    /// Attracting cycle near the orbit point `z`, see [`attracting_cycle`].
+    fn cycle(&self, z: ComplexDouble) -> Option<Cycle>;
+}
+
+/// Orbit of any fractal in `f64`.
+struct FractalOrbit<'a, F: ?Sized> {
+    fractal: &'a F,
+    c: ComplexDouble,
+    escape_radius: f64,
+}
+
+impl<F: Fractal + ?Sized> Orbit for FractalOrbit<'_, F> {
+    type Real = f64;
+
+    fn initial(&self) -> ComplexDouble {
+        self.fractal.initial(self.c)
+    }
+
+    fn initial_derivative(&self) -> ComplexDouble {
+        self.fractal.initial_derivative()
+    }
+
+    fn escaped(&self, z: ComplexDouble) -> bool {
+        self.fractal.escaped(z, self.escape_radius)
+    }
+
+    fn step(&self, z: ComplexDouble) -> ComplexDouble {
+        self.fractal.step(z, self.c)
+    }
+
+    fn derivative(&self, z: ComplexDouble, dz: ComplexDouble) -> Option<ComplexDouble> {
+        self.fractal.derivative(z, dz)
+    }
+
+    fn certainly_bounded(&self) -> bool {
+        self.fractal.certainly_bounded(self.c)
+    }
+
+    fn constant(&self) -> ComplexDouble {
+        self.fractal.constant(self.c)
+    }
+
+    fn periodicity_tolerance(&self) -> f64 {
+        PERIODICITY_TOLERANCE
+    }
+
+    fn cycle(&self, z: ComplexDouble) -> Option<Cycle> {
+        attracting_cycle(self.fractal, self.c, z, MAX_PERIOD)
+    }
+}
+
+/// Orbit of the Mandelbrot set in the precision `T`.
+struct MandelbrotOrbit<T> {
+    c: Complex<T>,
+    /// Square of the escape radius.
+    radius_sqr: T,
+}
+
+impl<T: Real> Orbit for MandelbrotOrbit<T> {
+    type Real = T;
+
+    fn initial(&self) -> Complex<T> {
+        Complex::zero()
+    }
+
+    fn initial_derivative(&self) -> Complex<T> {
+        Complex::zero()
+    }
+
+    fn escaped(&self, z: Complex<T>) -> bool {
+        z.norm_sqr() > self.radius_sqr
+    }
+
+    fn step(&self, z: Complex<T>) -> Complex<T> {
+        z * z + self.c
+    }
+
+    fn derivative(&self, z: Complex<T>, dz: Complex<T>) -> Option<Complex<T>> {
+        Some(z * dz * T::from_f64(2.) + T::one())
+    }
+
+    fn certainly_bounded(&self) -> bool {
+        in_cardioid_or_bulb(self.c)
+    }
+
+    fn constant(&self) -> ComplexDouble {
+        to_f64(self.c)
+    }
+
+    fn periodicity_tolerance(&self) -> T {
+        periodicity_tolerance()
+    }
+
+    fn cycle(&self, z: ComplexDouble) -> Option<Cycle> {
+        attracting_cycle(&Mandelbrot, to_f64(self.c), z, MAX_PERIOD)
+    }
+}
+
+fn to_f64<T: Real>(z: Complex<T>) -> ComplexDouble {
+    ComplexDouble::new(z.re.to_f64(), z.im.to_f64())
+}
+
+/// Iteration behind [`escape_with`] and [`mandelbrot_with`].
+fn iterate<O: Orbit>(orbit: &O, num_iterations: u32, track: Track) -> EscapeResult {
+    let mut z = orbit.initial();
+    let mut derivative = track.derivative.then(|| orbit.initial_derivative());
+    let mut min_distance = track.min_distance.then_some(f64::INFINITY);
+    let mut trap = track.trap.map(|_| TrapHit::default());
+    let mut average = track.average.map(|_| OrbitAverage::default());
+    let constant = orbit.constant();
+    let tolerance = orbit.periodicity_tolerance();
+
+    let early_out = !track.final_z;
+    let orbit_tracked = track.min_distance || track.cycle || track.trap.is_some();
+    let skip = early_out && !orbit_tracked && orbit.certainly_bounded();
+    let mut status = match skip {
+        true => Status::Periodic,
+        false => Status::Bounded,
+    };
+
+    //  Brent's cycle detection compares with the point saved after the last
+    //  power of two steps
+    let mut saved = z;
+    let (mut since_saved, mut save_after) = (0u32, 1u32);
+
+    if !skip {
+        for count in 0..=num_iterations {
+            if orbit.escaped(z) {
+                return EscapeResult {
+                    status: Status::Escaped,
+                    count,
+                    z: to_f64(z),
+                    derivative: derivative.map(to_f64),
+                    min_distance,
+                    cycle: None,
+                    trap,
+                    average,
+                };
+            }
+            if count == num_iterations {
+                break;
+            }
+
+            derivative = derivative.and_then(|dz| orbit.derivative(z, dz));
+            z = orbit.step(z);
+            let point = to_f64(z);
+            min_distance = min_distance.map(|distance| distance.min(point.norm()));
+            trap = trap
+                .zip(track.trap.as_ref())
+                .map(|(hit, shape)| hit.update(shape, point));
+            average = average
+                .zip(track.average.as_ref())
+                .map(|(average, statistic)| average.update(statistic, point, constant));
+
+            if early_out {
+                if (z - saved).norm_sqr() < tolerance * tolerance {
+                    status = Status::Periodic;
+                    break;
+                }
+                since_saved += 1;
+                if since_saved == save_after {
+                    saved = z;
+                    since_saved = 0;
+                    save_after = save_after.saturating_mul(2);
+                }
+            }
+        }
+    }
+
+    EscapeResult {
+        status,
+        count: num_iterations,
+        z: to_f64(z),
+        derivative: derivative.map(to_f64),
+        min_distance,
+        cycle: track.cycle.then(|| orbit.cycle(to_f64(z))).flatten(),
+        trap,
+        average,
+    }
+}
+
+/// Same as [`Mandelbrot::certainly_bounded`](Fractal::certainly_bounded) in
+/// the precision of `c`.
+fn in_cardioid_or_bulb<T: Real>(c: Complex<T>) -> bool {
+    let quarter = T::from_f64(0.25);
+    let x = c.re - quarter;
+    let im2 = c.im * c.im;
+    let q = x * x + im2;
+    let bulb = c.re + T::one();
+    q * (q + x) <= quarter * im2 || bulb * bulb + im2 <= T::from_f64(1. / 16.)
+}
+
+/// Continuous version of the escape count `count` of an orbit that escaped
+/// to `z`, for a fractal of the given `degree`.
+///
+/// The fractional part interpolates between neighbouring counts by how far
+/// beyond `escape_radius` the orbit got, so the result lies in
+/// `count - 1..count` and is continuous across the boundaries of the escape
+/// bands. The larger `escape_radius`, the more accurate the interpolation.
+pub fn smooth_count(count: u32, z: ComplexDouble, escape_radius: f64, degree: f64) -> f64 {
+    count as f64 - (z.norm().ln() / escape_radius.ln()).ln() / degree.ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fractal::Julia;
+
+    #[test]
+    fn mandelbrot_test() {
+        const NUM_ITERATIONS: u32 = 20;
+
+        //  Not in the mandelbrot set
+        let z1 = ComplexDouble::new(0.25, 0.75);
+        assert_ne!(mandelbrot(&z1, NUM_ITERATIONS), NUM_ITERATIONS);
+
+        let z2 = ComplexDouble::new(-1., 0.5);
+        assert_ne!(mandelbrot(&z2, NUM_ITERATIONS), NUM_ITERATIONS);
+
+        //  In the mandelbrot set
+        let z3 = ComplexDouble::new(0., 0.);
+        assert_eq!(mandelbrot(&z3, NUM_ITERATIONS), NUM_ITERATIONS);
+
+        let z4 = ComplexDouble::new(1. / 8., -1. / 8.);
+        assert_eq!(mandelbrot(&z4, NUM_ITERATIONS), NUM_ITERATIONS);
+    }
+
+    #[test]
+    fn escape_radius_test() {
+        let c = ComplexDouble::new(0.25, 0.75);
+
+        assert_eq!(
+            escape_count(&Mandelbrot, &c, 100, DEFAULT_ESCAPE_RADIUS),
+            mandelbrot(&c, 100)
+        );
+        assert!(escape_count(&Mandelbrot, &c, 100, 1e3) > mandelbrot(&c, 100));
+    }
+
+    #[test]
+    fn smooth_count_test() {
+        //  Walk along the real axis towards the set, crossing several bands
+        let smooth = |re: f64| {
+            let c = ComplexDouble::new(re, 0.);
+            let result = escape(&Mandelbrot, &c, 1000, SMOOTH_ESCAPE_RADIUS);
+            let value = smooth_count(result.count, result.z, SMOOTH_ESCAPE_RADIUS, 2.);
+            (result.count, value)
+        };
+
+        let first = smooth(1.);
+        let mut previous = first;
+        for step in 1..=6000 {
+            let (count, value) = smooth(1. - step as f64 * 1e-4);
+            assert!(value <= count as f64 && value > count as f64 - 1.);
+            assert!((value - previous.1).abs() < 0.01, "jump at step {}", step);
+            previous = (count, value);
+        }
+        assert!(previous.0 > first.0 + 3);
+    }
+
+    #[test]
+    fn status_test() {
+        //  0.25 + 0.75i escapes after exactly 5 iterations
+        let c = ComplexDouble::new(0.25, 0.75);
+        assert_eq!(mandelbrot(&c, 5), 5);
+        assert!(escape(&Mandelbrot, &c, 5, DEFAULT_ESCAPE_RADIUS).escaped());
+        assert!(!escape(&Mandelbrot, &c, 4, DEFAULT_ESCAPE_RADIUS).escaped());
+
+        let c = ComplexDouble::new(-1., 0.);
+        let track = Track {
+            final_z: true,
+            ..Track::default()
+        };
+        let result = escape_with(&Mandelbrot, &c, 9, DEFAULT_ESCAPE_RADIUS, track);
+        assert_eq!(result.status, Status::Bounded);
+        assert_eq!((result.count, result.z), (9, ComplexDouble::new(-1., 0.)));
+        assert_eq!(result.derivative, None);
+
+        //  The early-outs keep the count
+        let result = escape(&Mandelbrot, &c, 9, DEFAULT_ESCAPE_RADIUS);
+        assert_eq!((result.status, result.count), (Status::Periodic, 9));
+    }
+
+    #[test]
+    fn exterior_distance_test() {
+        //  The filled Julia set of 0 is the unit disk
+        let julia = Julia {
+            c: ComplexDouble::new(0., 0.),
+        };
+        let track = Track {
+            derivative: true,
+            ..Track::default()
+        };
+        for distance in [1e-6, 1e-3, 0.1, 1.] {
+            let z = ComplexDouble::from_polar(1. + distance, 0.7);
+            let result = escape_with(&julia, &z, 1000, SMOOTH_ESCAPE_RADIUS, track);
+            let estimate = result.exterior_distance().unwrap();
+            assert!(distance <= estimate && estimate <= 4. * distance);
+        }
+
+        let bounded = escape_with(&julia, &ComplexDouble::new(0.5, 0.), 100, 2., track);
+        assert_eq!(bounded.exterior_distance(), None);
+    }
+
+    #[test]
+    fn track_test() {
+        let track = Track {
+            derivative: true,
+            min_distance: true,
+            final_z: true,
+            ..Track::default()
+        };
+        let c = ComplexDouble::new(0.3, 0.2);
+        let h = 1e-7;
+        let z = |c| {
+            let track = Track {
+                final_z: true,
+                ..Track::default()
+            };
+            escape_with(&Mandelbrot, &c, 5, 1e10, track).z
+        };
+        let result = escape_with(&Mandelbrot, &c, 5, 1e10, track);
+
+        //  Compare with a finite difference
+        let difference = (z(c + h) - z(c)) / h;
+        let derivative = result.derivative.unwrap();
+        assert!((derivative - difference).norm() < 1e-4 * derivative.norm());
+
+        //  The orbit starts at c, 0.3 + 0.2i
+        assert!(result.min_distance.unwrap() <= c.norm());
+        assert_eq!(
+            escape_with(&Mandelbrot, &ComplexDouble::new(0., 0.), 5, 2., track).min_distance,
+            Some(0.)
+        );
+    }
+
+    #[test]
+    fn trap_test() {
+        //  A point trap at the origin is as close as the orbit gets to it
+        let track = Track {
+            min_distance: true,
+            trap: Some(TrapShape::Point {
+                center: ComplexDouble::new(0., 0.),
+            }),
+            ..Track::default()
+        };
+        for c in [(0.3, 0.2), (-0.1, 0.1), (-1.2, 0.3), (0.5, 0.5)] {
+            let c = ComplexDouble::new(c.0, c.1);
+            let result = escape_with(&Mandelbrot, &c, 50, 2., track);
+            let hit = result.trap.unwrap();
+            assert_eq!(Some(hit.distance), result.min_distance);
+            assert_eq!(hit.z.norm(), hit.distance);
+            assert_eq!(mandelbrot_with(&c, 50, 2., track).trap, Some(hit));
+        }
+        assert_eq!(
+            escape(&Mandelbrot, &ComplexDouble::new(0.3, 0.2), 50, 2.).trap,
+            None
+        );
+    }
+
+    #[test]
+    fn attracting_cycle_test() {
+        let cycle = |re, im| {
+            let c = ComplexDouble::new(re, im);
+            let track = Track {
+                cycle: true,
+                ..Track::default()
+            };
+            escape_with(&Mandelbrot, &c, 200, 2., track).cycle
+        };
+
+        //  Center of the main cardioid, 0.25 from its cusp
+        let center = cycle(0., 0.).unwrap();
+        assert_eq!((center.period, center.multiplier.norm()), (1, 0.));
+        let distance = center.interior_distance.unwrap();
+        assert!(distance / 4. <= 0.25 && 0.25 <= distance);
+
+        //  The fixed point (1 - sqrt(1 - 4c)) / 2 has multiplier 2z
+        let c = ComplexDouble::new(-0.5, 0.1);
+        let fixed = (1. - (1. - 4. * c).sqrt()) / 2.;
+        let cardioid = cycle(c.re, c.im).unwrap();
+        assert_eq!(cardioid.period, 1);
+        assert!((cardioid.multiplier - 2. * fixed).norm() < 1e-9);
+
+        assert_eq!(cycle(-1., 0.).unwrap().period, 2);
+        assert_eq!(cycle(-0.122, 0.745).unwrap().period, 3);
+        assert_eq!(cycle(-1.3107, 0.).unwrap().period, 4);
+
+        //  No cycle outside the set or without partial derivatives
+        assert_eq!(cycle(1., 1.), None);
+        let c = ComplexDouble::new(-0.1, 0.1);
+        assert_eq!(attracting_cycle(&crate::fractal::Tricorn, c, c, 10), None);
+    }
+
+    #[test]
+    fn early_out_test() {
+        use crate::fractal::{BurningShip, Fractal, Multibrot};
+
+        let brute_force = Track {
+            final_z: true,
+            ..Track::default()
+        };
+        fn compare<F: Fractal>(fractal: &F, re: std::ops::Range<f64>, brute_force: Track) {
+            let mut bounded = 0;
+            for i in 0..60 {
+                for j in 0..60 {
+                    let c = ComplexDouble::new(
+                        re.start + (re.end - re.start) * i as f64 / 60.,
+                        -1.2 + 2.4 * j as f64 / 60.,
+                    );
+                    let fast = escape(fractal, &c, 2000, 2.);
+                    let slow = escape_with(fractal, &c, 2000, 2., brute_force);
+                    assert_eq!(
+                        (fast.escaped(), fast.count),
+                        (slow.escaped(), slow.count),
+                        "{}",
+                        c
+                    );
+                    assert_ne!(slow.status, Status::Periodic);
+                    bounded += !slow.escaped() as u32;
+                }
+            }
+            assert!(bounded > 100, "{}", bounded);
+        }
+
+        compare(&Mandelbrot, -2.1..0.6, brute_force);
+        compare(&Multibrot { power: 3 }, -1.2..1.2, brute_force);
+        compare(&BurningShip, -2.1..1.2, brute_force);
+        let rabbit = Julia {
+            c: ComplexDouble::new(-0.122, 0.745),
+        };
+        compare(&rabbit, -1.6..1.6, brute_force);
+    }
+
+    #[test]
+    fn precision_test() {
+        use crate::double_double::DoubleDouble;
+
+        //  f64 gives the same results as the Fractal kernel, the other
+        //  precisions the same counts for all but a few boundary points
+        let mut differences = (0, 0);
+        for i in 0..60 {
+            for j in 0..60 {
+                let c = ComplexDouble::new(
+                    -2.1 + 2.7 * (i as f64 + 0.5) / 60.,
+                    -1.2 + 2.4 * (j as f64 + 0.5) / 60.,
+                );
+                let expected = escape(&Mandelbrot, &c, 1000, 2.);
+                let double = mandelbrot_with(&c, 1000, 2., Track::default());
+                assert_eq!(
+                    (double.escaped(), double.count),
+                    (expected.escaped(), expected.count)
+                );
+
+                let single = mandelbrot(&Complex::new(c.re as f32, c.im as f32), 1000);
+                let double_double = mandelbrot(
+                    &Complex::new(DoubleDouble::new(c.re), DoubleDouble::new(c.im)),
+                    1000,
+                );
+                differences.0 += (single != expected.count) as u32;
+                differences.1 += (double_double != expected.count) as u32;
+            }
+        }
+        assert!(differences.0 <= 20, "{:?}", differences);
+        assert!(differences.1 <= 2, "{:?}", differences);
+    }
+
+    #[test]
+    fn certainly_bounded_test() {
+        use crate::fractal::Fractal;
+
+        //  Both regions are inside the set, with boundaries at the given points
+        for (re, im, inside) in [
+            (0., 0., true),
+            (-0.74, 0., true),
+            (-0.76, 0., true),
+            (-1.24, 0., true),
+            (0.24, 0., true),
+            (0.26, 0., false),
+            (-1.26, 0., false),
+            (-0.1, 0.63, true),
+            (-0.1, 0.66, false),
+        ] {
+            let c = ComplexDouble::new(re, im);
+            assert_eq!(Mandelbrot.certainly_bounded(c), inside, "{}", c);
+            if inside {
+                assert_eq!(mandelbrot(&c, 10000), 10000);
+            }
+        }
+    }
+}
@@ -0,0 +1,338 @@
+//! Escape-time fractals that can be rendered.
+//!
+//! Every fractal is an iteration $z_{n+1} = f(z_n, c)$ started from a point
+//! that depends on the sampled pixel `c`. The [`Fractal`] trait describes one
+//! such iteration, [`FractalKind`] selects one of the built-in ones in scenes
+//! and on the command line.
+
+use serde::{Deserialize, Serialize};
+
+use crate::escape::ComplexDouble;
+
+/// Iteration rule of an escape-time fractal.
+pub trait Fractal {
+    /// First point of the orbit of the pixel `c`.
+    fn initial(&self, c: ComplexDouble) -> ComplexDouble;
+
+    /// Next point of the orbit after `z`.
+    fn step(&self, z: ComplexDouble, c: ComplexDouble) -> ComplexDouble;
+
+    /// Constant added at every step for the pixel `c`.
+    fn constant(&self, c: ComplexDouble) -> ComplexDouble {
+        c
+    }
+
+    /// Whether the orbit has escaped once it reached `z`.
+    fn escaped(&self, z: ComplexDouble, escape_radius: f64) -> bool {
+        z.norm() > escape_radius
+    }
+
+    /// Derivative of the initial point with respect to the pixel.
+    fn initial_derivative(&self) -> ComplexDouble {
+        ComplexDouble::new(0., 0.)
+    }
+
+    /// Derivative of the point after `z` with respect to the pixel, where `dz`
+    /// is the derivative of `z`, or `None` if the iteration isn't holomorphic.
+    fn derivative(&self, _z: ComplexDouble, _dz: ComplexDouble) -> Option<ComplexDouble> {
+        None
+    }
+
+    /// Partial derivatives of one step at `z` for the pixel `c`, or `None` if
+    /// the iteration isn't holomorphic.
+    fn partials(&self, _z: ComplexDouble, _c: ComplexDouble) -> Option<Partials> {
+        None
+    }
+
+    /// Whether the orbit of the pixel `c` is known to stay bounded without
+    /// iterating it.
+    fn certainly_bounded(&self, _c: ComplexDouble) -> bool {
+        false
+    }
+
+    /// Exponent $d$ with which escaping orbits grow like $|z|^d$ per step.
+    fn degree(&self) -> f64 {
+        2.
+    }
+}
+
+/// Partial derivatives of one step $f(z, c)$ of an iteration, `c` being the
+/// pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Partials {
+    /// $\partial f / \partial z$
+    pub dz: ComplexDouble,
+    /// $\partial^2 f / \partial z^2$
+    pub dzdz: ComplexDouble,
+    /// $\partial f / \partial c$
+    pub dc: ComplexDouble,
+    /// $\partial^2 f / \partial c \partial z$
+    pub dcdz: ComplexDouble,
+}
+
+impl Partials {
+    /// Partials of $z^d + a c$, `a` being `1` when the pixel is the parameter
+    /// and `0` when it is the starting point.
+    fn power(z: ComplexDouble, d: u32, a: f64) -> Self {
+        let d_ = d as f64;
+        Partials {
+            dz: d_ * z.powu(d - 1),
+            dzdz: d_ * (d_ - 1.) * z.powu(d.saturating_sub(2)),
+            dc: ComplexDouble::new(a, 0.),
+            dcdz: ComplexDouble::new(0., 0.),
+        }
+    }
+}
+
+/// The Mandelbrot set, $z^2 + c$ starting from $z = 0$.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Mandelbrot;
+
+impl Fractal for Mandelbrot {
+    fn initial(&self, _: ComplexDouble) -> ComplexDouble {
+        ComplexDouble::new(0., 0.)
+    }
+
+    fn step(&self, z: ComplexDouble, c: ComplexDouble) -> ComplexDouble {
+        z.powi(2) + c
+    }
+
+    fn derivative(&self, z: ComplexDouble, dz: ComplexDouble) -> Option<ComplexDouble> {
+        Some(2. * z * dz + 1.)
+    }
+
+    fn partials(&self, z: ComplexDouble, _: ComplexDouble) -> Option<Partials> {
+        Some(Partials::power(z, 2, 1.))
+    }
+
+    /// Points inside the main cardioid or the period 2 bulb.
+    fn certainly_bounded(&self, c: ComplexDouble) -> bool {
+        let x = c.re - 0.25;
+        let q = x * x + c.im * c.im;
+        let cardioid = q * (q + x) <= 0.25 * c.im * c.im;
+        let bulb = (c.re + 1.).powi(2) + c.im * c.im <= 1. / 16.;
+        cardioid || bulb
+    }
+}
+
+/// Filled Julia set of $z^2 + c$ for a fixed `c`; the pixel is the starting
+/// point of the orbit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Julia {
+    pub c: ComplexDouble,
+}
+
+impl Fractal for Julia {
+    fn initial(&self, z: ComplexDouble) -> ComplexDouble {
+        z
+    }
+
+    fn step(&self, z: ComplexDouble, _: ComplexDouble) -> ComplexDouble {
+        z.powi(2) + self.c
+    }
+
+    fn constant(&self, _: ComplexDouble) -> ComplexDouble {
+        self.c
+    }
+
+    fn initial_derivative(&self) -> ComplexDouble {
+        ComplexDouble::new(1., 0.)
+    }
+
+    fn derivative(&self, z: ComplexDouble, dz: ComplexDouble) -> Option<ComplexDouble> {
+        Some(2. * z * dz)
+    }
+
+    fn partials(&self, z: ComplexDouble, _: ComplexDouble) -> Option<Partials> {
+        Some(Partials::power(z, 2, 0.))
+    }
+}
+
+/// The Burning Ship, $(|\Re z| + i|\Im z|)^2 + c$ starting from $z = 0$.
+///
+/// The ship sails upside down unless the imaginary axis is flipped, e.g. by
+/// viewing the conjugate region.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BurningShip;
+
+impl Fractal for BurningShip {
+    fn initial(&self, _: ComplexDouble) -> ComplexDouble {
+        ComplexDouble::new(0., 0.)
+    }
+
+    fn step(&self, z: ComplexDouble, c: ComplexDouble) -> ComplexDouble {
+        ComplexDouble::new(z.re.abs(), z.im.abs()).powi(2) + c
+    }
+}
+
+/// The Tricorn or Mandelbar, $\bar{z}^2 + c$ starting from $z = 0$.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Tricorn;
+
+impl Fractal for Tricorn {
+    fn initial(&self, _: ComplexDouble) -> ComplexDouble {
+        ComplexDouble::new(0., 0.)
+    }
+
+    fn step(&self, z: ComplexDouble, c: ComplexDouble) -> ComplexDouble {
+        z.conj().powi(2) + c
+    }
+}
+
+/// Multibrot set of degree `power`, $z^d + c$ starting from $z = 0$.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Multibrot {
+    pub power: u32,
+}
+
+impl Fractal for Multibrot {
+    fn initial(&self, _: ComplexDouble) -> ComplexDouble {
+        ComplexDouble::new(0., 0.)
+    }
+
+    fn step(&self, z: ComplexDouble, c: ComplexDouble) -> ComplexDouble {
+        z.powu(self.power) + c
+    }
+
+    fn derivative(&self, z: ComplexDouble, dz: ComplexDouble) -> Option<ComplexDouble> {
+        Some(self.power as f64 * z.powu(self.power - 1) * dz + 1.)
+    }
+
+    fn partials(&self, z: ComplexDouble, _: ComplexDouble) -> Option<Partials> {
+        Some(Partials::power(z, self.power, 1.))
+    }
+
+    fn degree(&self) -> f64 {
+        self.power as f64
+    }
+}
+
+/// Smallest degree of a Multibrot set.
+pub const MIN_POWER: u32 = 2;
+
+/// One of the built-in fractals.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum FractalKind {
+    /// See [`Mandelbrot`].
+    #[default]
+    Mandelbrot,
+    /// See [`Julia`].
+    Julia { c: ComplexDouble },
+    /// See [`BurningShip`].
+    BurningShip,
+    /// See [`Tricorn`].
+    Tricorn,
+    /// See [`Multibrot`].
+    Multibrot { power: u32 },
+}
+
+impl FractalKind {
+    /// Checks the parameters of the fractal.
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            FractalKind::Julia { c } if !(c.re.is_finite() && c.im.is_finite()) => {
+                Err(format!("Julia parameter {} is not finite", c))
+            }
+            FractalKind::Multibrot { power } if power < MIN_POWER => Err(format!(
+                "Multibrot power {} is smaller than {}",
+                power, MIN_POWER
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the iteration is holomorphic, so that orbits have a
+    /// derivative, see [`Fractal::derivative`].
+    pub fn has_derivative(&self) -> bool {
+        !matches!(self, FractalKind::BurningShip | FractalKind::Tricorn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::escape::escape_count;
+
+    const NUM_ITERATIONS: u32 = 50;
+
+    fn escapes<F: Fractal>(fractal: &F, re: f64, im: f64) -> bool {
+        escape_count(fractal, &ComplexDouble::new(re, im), NUM_ITERATIONS, 2.) != NUM_ITERATIONS
+    }
+
+    #[test]
+    fn julia_test() {
+        //  The Julia set of 0 is the unit disk
+        let julia = Julia {
+            c: ComplexDouble::new(0., 0.),
+        };
+        assert!(!escapes(&julia, 0.6, -0.7));
+        assert!(escapes(&julia, 0.8, -0.7));
+    }
+
+    #[test]
+    fn burning_ship_test() {
+        //  Real orbits only differ from the Mandelbrot set by signs
+        for re in [-1.9, -1.5, -0.5, 0.2, 0.3] {
+            assert_eq!(escapes(&BurningShip, re, 0.), escapes(&Mandelbrot, re, 0.));
+        }
+        assert!(!escapes(&BurningShip, -0.5, -0.5));
+        assert!(escapes(&BurningShip, -0.5, 0.5));
+    }
+
+    #[test]
+    fn tricorn_test() {
+        //  The Tricorn is symmetric under conjugation and threefold rotation
+        let rotation = ComplexDouble::from_polar(1., 2. * std::f64::consts::PI / 3.);
+        for c in [ComplexDouble::new(-0.1, 0.1), ComplexDouble::new(0.3, 0.1)] {
+            let count = |c: ComplexDouble| escape_count(&Tricorn, &c, NUM_ITERATIONS, 2.);
+            assert_eq!(count(c), count(c.conj()));
+            assert_eq!(count(c), count(c * rotation));
+        }
+    }
+
+    #[test]
+    fn multibrot_test() {
+        let square = Multibrot { power: 2 };
+        for (re, im) in [(0.25, 0.75), (-1., 0.5), (0., 0.), (0.125, -0.125)] {
+            assert_eq!(escapes(&square, re, im), escapes(&Mandelbrot, re, im));
+        }
+
+        let cube = Multibrot { power: 3 };
+        assert!(!escapes(&cube, 0., 0.5));
+        assert!(escapes(&cube, 0.5, 0.));
+    }
+
+    #[test]
+    fn derivative_test() {
+        let (z, dz) = (ComplexDouble::new(0.5, -1.), ComplexDouble::new(2., 1.));
+        assert_eq!(
+            Multibrot { power: 2 }.derivative(z, dz),
+            Mandelbrot.derivative(z, dz)
+        );
+        assert_eq!(BurningShip.derivative(z, dz), None);
+        assert_eq!(Tricorn.derivative(z, dz), None);
+        assert!(!FractalKind::Tricorn.has_derivative());
+        assert!(FractalKind::Multibrot { power: 3 }.has_derivative());
+
+        let cube = Multibrot { power: 3 }.partials(z, z).unwrap();
+        assert_eq!(cube.dz, 3. * z * z);
+        assert_eq!(cube.dzdz, 6. * z);
+        assert_eq!(
+            Mandelbrot.partials(z, z).unwrap().dzdz,
+            ComplexDouble::new(2., 0.)
+        );
+        assert_eq!(Tricorn.partials(z, z), None);
+    }
+
+    #[test]
+    fn validate_test() {
+        assert!(FractalKind::Multibrot { power: 1 }.validate().is_err());
+        assert!(FractalKind::Julia {
+            c: ComplexDouble::new(f64::NAN, 0.)
+        }
+        .validate()
+        .is_err());
+        assert!(FractalKind::Tricorn.validate().is_ok());
+    }
+}
@@ -0,0 +1,45 @@
+//! Escape-time rendering of the [Mandelbrot](https://en.wikipedia.org/wiki/Mandelbrot_set) set.
+//!
+//! * [`escape`] iterates single points of the complex plane.
+//! * [`precision`] and [`double_double`] iterate points with `f32` or with
+//!   enough precision for zooms to about `1e28`.
+//! * [`simd`] iterates several points at a time with vector instructions.
+//! * [`fractal`] selects the fractal that is iterated.
+//! * [`view`] maps the pixel grid onto a window of the complex plane.
+//! * [`sampling`] lays out the samples of supersampled pixels.
+//! * [`color`] turns escape counts into pixel colors, some of them through
+//!   [`average`]s of a statistic over the orbit.
+//! * [`lighting`] shades the outside of the set as a lit relief.
+//! * [`trap`] colors points by how close their orbits come to a shape.
+//! * [`palette`] maps palette positions to colors through gradients.
+//! * [`subdivide`] skips iterating uniform regions of an image.
+//! * [`perturbation`] renders deep zooms relative to a reference orbit
+//!   computed with [`bignum`] numbers.
+//! * [`render`] computes whole images into a [`buffer::Buffer`] and draws
+//!   them onto a plotters backend or into a file.
+//! * [`scene`] loads and stores complete renders as TOML or JSON files.
+//!
+//! ```no_run
+//! use mandelbrot::render::{render_to_file, Settings};
+//!
+//! render_to_file("mandelbrot.png", &Settings::default()).unwrap();
+//! ```
+
+pub mod average;
+pub mod bignum;
+pub mod buffer;
+pub mod color;
+pub mod double_double;
+pub mod escape;
+pub mod fractal;
+pub mod lighting;
+pub mod palette;
+pub mod perturbation;
+pub mod precision;
+pub mod render;
+pub mod sampling;
+pub mod scene;
+pub mod simd;
+pub mod subdivide;
+pub mod trap;
+pub mod view;
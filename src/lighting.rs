@@ -0,0 +1,207 @@
+//! Shading of the outside of the set as a lit relief.
+//!
+//! Near the set, the exterior distance estimate grows in the direction of
+//! $u = z / z'$, where $z'$ is the derivative of the final point of the orbit
+//! with respect to the point of the pixel: $c$ for the Mandelbrot set and its
+//! variants, the starting point $z_0$ for Julia sets. Tilting $u$ out of the
+//! plane by a height gives a surface normal, which [`Lighting`] lights with a
+//! diffuse and a specular term and blends with the palette color.
+
+use plotters::style::RGBColor;
+use serde::{Deserialize, Serialize};
+
+use crate::escape::ComplexDouble;
+use crate::palette::{from_linear, to_linear};
+
+/// Light shining on the relief. Angles are in degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Lighting {
+    /// Direction the light comes from, counterclockwise from the real axis.
+    pub azimuth: f64,
+    /// Angle of the light above the plane, `90` straight from the viewer.
+    pub elevation: f64,
+    /// Vertical part of the surface normals next to their horizontal part of
+    /// length `1`: the higher, the flatter the relief looks.
+    pub height: f64,
+    /// Brightness of the highlights, in `0..=1`.
+    pub specular: f64,
+    /// Exponent of the highlights: the higher, the smaller and sharper.
+    pub shininess: f64,
+    /// Share of the lit color in the blend with the palette color, in
+    /// `0..=1`.
+    pub strength: f64,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Lighting {
+            azimuth: 45.,
+            elevation: 45.,
+            height: 1.,
+            specular: 0.4,
+            shininess: 20.,
+            strength: 1.,
+        }
+    }
+}
+
+impl Lighting {
+    /// Checks the parameters of the light.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.azimuth.is_finite() {
+            return Err(format!("light azimuth {} is not finite", self.azimuth));
+        }
+        if !(0. ..=90.).contains(&self.elevation) {
+            return Err(format!(
+                "light elevation {} is not between 0 and 90 degrees",
+                self.elevation
+            ));
+        }
+        for (name, value) in [("height", self.height), ("shininess", self.shininess)] {
+            if !(value > 0. && value.is_finite()) {
+                return Err(format!("light {} {} is not a positive number", name, value));
+            }
+        }
+        for (name, value) in [("specular", self.specular), ("strength", self.strength)] {
+            if !(0. ..=1.).contains(&value) {
+                return Err(format!("light {} {} is not between 0 and 1", name, value));
+            }
+        }
+        Ok(())
+    }
+
+    /// Unit vector pointing towards the light.
+    fn direction(&self) -> [f64; 3] {
+        let (azimuth, elevation) = (self.azimuth.to_radians(), self.elevation.to_radians());
+        [
+            elevation.cos() * azimuth.cos(),
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+        ]
+    }
+
+    /// Brightness of the relief, diffuse and specular, where the orbit
+    /// escaped to `z` with the derivative `derivative`, or `None` where the
+    /// normal isn't defined.
+    pub fn brightness(&self, z: ComplexDouble, derivative: ComplexDouble) -> Option<(f64, f64)> {
+        let u = z / derivative;
+        let u = u / u.norm();
+        if !(u.re.is_finite() && u.im.is_finite()) {
+            return None;
+        }
+        let normal = normalize([u.re, u.im, self.height]);
+        let light = self.direction();
+        let diffuse = dot(normal, light).max(0.);
+        //  Blinn-Phong highlight, the viewer looking straight down
+        let halfway = normalize([light[0], light[1], light[2] + 1.]);
+        let specular = self.specular * dot(normal, halfway).max(0.).powf(self.shininess);
+        Some((diffuse, specular))
+    }
+
+    /// `color` of a point whose orbit escaped to `z` with the derivative
+    /// `derivative`, lit and blended by `strength` in linear light.
+    pub fn shade(&self, color: RGBColor, z: ComplexDouble, derivative: ComplexDouble) -> RGBColor {
+        let Some((diffuse, specular)) = self.brightness(z, derivative) else {
+            return color;
+        };
+        let flat = to_linear(color);
+        let lit = flat.map(|channel| channel * diffuse + specular);
+        let mut mixed = [0.; 3];
+        for channel in 0..3 {
+            mixed[channel] = flat[channel] + self.strength * (lit[channel] - flat[channel]);
+        }
+        from_linear(mixed)
+    }
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(v: [f64; 3]) -> [f64; 3] {
+    let norm = dot(v, v).sqrt();
+    v.map(|x| x / norm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn brightness_test() {
+        let lighting = Lighting {
+            azimuth: 0.,
+            elevation: 0.,
+            specular: 0.,
+            ..Lighting::default()
+        };
+        let dz = ComplexDouble::new(0., 2.);
+
+        //  Slopes facing the light are brighter than those facing away
+        let (facing, _) = lighting.brightness(ComplexDouble::new(0., 3.), dz).unwrap();
+        let (away, _) = lighting
+            .brightness(ComplexDouble::new(0., -3.), dz)
+            .unwrap();
+        assert!((facing - 0.5f64.sqrt()).abs() < 1e-12);
+        assert_eq!(away, 0.);
+
+        let zero = ComplexDouble::new(0., 0.);
+        assert_eq!(lighting.brightness(zero, dz), None);
+        assert_eq!(lighting.brightness(dz, zero), None);
+    }
+
+    #[test]
+    fn shade_test() {
+        let color = RGBColor(200, 100, 50);
+        let z = ComplexDouble::new(1., 0.);
+        let dz = ComplexDouble::new(1., 0.);
+        let overhead = Lighting {
+            elevation: 90.,
+            height: 1e9,
+            specular: 0.,
+            ..Lighting::default()
+        };
+        //  A flat relief lit from above keeps its color
+        assert_eq!(overhead.shade(color, z, dz), color);
+
+        let unlit = Lighting {
+            azimuth: 180.,
+            elevation: 0.,
+            ..overhead
+        };
+        assert_eq!(unlit.shade(color, z, dz), RGBColor(0, 0, 0));
+        let half = Lighting {
+            strength: 0.5,
+            ..unlit
+        };
+        let RGBColor(r, _, _) = half.shade(color, z, dz);
+        assert!(r > 0 && r < 200);
+        assert_eq!(half.shade(color, z, ComplexDouble::new(0., 0.)), color);
+    }
+
+    #[test]
+    fn validate_test() {
+        assert!(Lighting::default().validate().is_ok());
+        for lighting in [
+            Lighting {
+                elevation: 91.,
+                ..Lighting::default()
+            },
+            Lighting {
+                height: 0.,
+                ..Lighting::default()
+            },
+            Lighting {
+                specular: 2.,
+                ..Lighting::default()
+            },
+            Lighting {
+                azimuth: f64::NAN,
+                ..Lighting::default()
+            },
+        ] {
+            assert!(lighting.validate().is_err());
+        }
+    }
+}
@@ -0,0 +1,694 @@
+//! Palettes mapping positions in `0..=1` to colors.
+//!
+//! A [`Palette`] runs through a [`Gradient`], either a built-in one or a list
+//! of color stops, possibly several times and shifted by an offset. Colors
+//! between stops are mixed in a selectable [`ColorSpace`]. Gradients can be
+//! imported from Fractint `.map` and GIMP `.ggr` files.
+
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use plotters::style::{Color, HSLColor, RGBColor};
+use serde::{Deserialize, Serialize};
+
+/// Colors a palette position in `0..=1` is mapped to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Palette {
+    /// Colors of one run through the palette.
+    pub gradient: Gradient,
+    /// Color space the colors between two stops are mixed in.
+    pub space: ColorSpace,
+    /// Number of times the gradient is run through from position `0` to `1`.
+    pub repeat: f64,
+    /// Shift of the gradient, as a fraction of one run through it.
+    pub offset: f64,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            gradient: Gradient::default(),
+            space: ColorSpace::default(),
+            repeat: 1.,
+            offset: 0.,
+        }
+    }
+}
+
+impl Palette {
+    /// Palette running once through `gradient`.
+    pub fn new(gradient: Gradient) -> Self {
+        Palette {
+            gradient,
+            ..Palette::default()
+        }
+    }
+
+    /// Color at position `t` of the palette.
+    ///
+    /// Positions past the end of the gradient wrap around to its start, so
+    /// each run through the gradient ends with its last color.
+    pub fn color(&self, t: f64) -> RGBColor {
+        let u = self.repeat * t + self.offset;
+        let fraction = u - u.floor();
+        let u = if fraction == 0. && u > 0. {
+            1.
+        } else {
+            fraction
+        };
+        self.gradient.color(u, self.space)
+    }
+
+    /// Checks that the palette maps every position to a color.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.repeat > 0. && self.repeat.is_finite()) {
+            return Err(format!(
+                "palette repeat {} is not a positive number",
+                self.repeat
+            ));
+        }
+        if !self.offset.is_finite() {
+            return Err(format!("palette offset {} is not finite", self.offset));
+        }
+        self.gradient.validate()
+    }
+}
+
+/// Color spaces colors can be mixed in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorSpace {
+    /// Gamma-encoded sRGB values, as stored in image files.
+    #[default]
+    Srgb,
+    /// Linear-light RGB, the physically correct mix of two lights.
+    LinearRgb,
+    /// [Oklab](https://bottosson.github.io/posts/oklab/), a perceptually
+    /// uniform space giving even steps in lightness and hue.
+    Oklab,
+}
+
+impl ColorSpace {
+    /// Color a fraction `f` of the way from `a` to `b`.
+    pub fn mix(&self, a: RGBColor, b: RGBColor, f: f64) -> RGBColor {
+        match self {
+            ColorSpace::Srgb => {
+                let mix = |a: u8, b: u8| (a as f64 + f * (b as f64 - a as f64)).round() as u8;
+                RGBColor(mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2))
+            }
+            ColorSpace::LinearRgb => from_linear(lerp(to_linear(a), to_linear(b), f)),
+            ColorSpace::Oklab => {
+                let mixed = lerp(to_oklab(to_linear(a)), to_oklab(to_linear(b)), f);
+                from_linear(from_oklab(mixed))
+            }
+        }
+    }
+}
+
+/// Linear-light value in `0..=1` of the sRGB channel value `value`.
+pub fn decode_srgb(value: u8) -> f64 {
+    let value = value as f64 / 255.;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// sRGB channel value of the linear-light value `value`, clamped to `0..=1`.
+pub fn encode_srgb(value: f64) -> u8 {
+    let value = value.clamp(0., 1.);
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1. / 2.4) - 0.055
+    };
+    (value * 255.).round() as u8
+}
+
+/// Linear-light RGB values of `color`.
+pub fn to_linear(RGBColor(r, g, b): RGBColor) -> [f64; 3] {
+    [decode_srgb(r), decode_srgb(g), decode_srgb(b)]
+}
+
+/// Color with the linear-light RGB values `rgb`.
+pub fn from_linear([r, g, b]: [f64; 3]) -> RGBColor {
+    RGBColor(encode_srgb(r), encode_srgb(g), encode_srgb(b))
+}
+
+/// Average of `colors`, at least one, taken in linear light so that mixing
+/// colors keeps their brightness.
+pub fn average<I: IntoIterator<Item = RGBColor>>(colors: I) -> RGBColor {
+    let (mut sum, mut count) = ([0.; 3], 0);
+    for color in colors {
+        let rgb = to_linear(color);
+        for channel in 0..3 {
+            sum[channel] += rgb[channel];
+        }
+        count += 1;
+    }
+    from_linear(sum.map(|channel| channel / count as f64))
+}
+
+fn lerp(a: [f64; 3], b: [f64; 3], f: f64) -> [f64; 3] {
+    [0, 1, 2].map(|i| a[i] + f * (b[i] - a[i]))
+}
+
+fn to_oklab([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+fn from_oklab([l, a, b]: [f64; 3]) -> [f64; 3] {
+    let l_ = (l + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m_ = (l - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s_ = (l - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+    [
+        4.0767416621 * l_ - 3.3077115913 * m_ + 0.2309699292 * s_,
+        -1.2684380046 * l_ + 2.6097574011 * m_ - 0.3413193965 * s_,
+        -0.0041960863 * l_ - 0.7034186147 * m_ + 1.7076147010 * s_,
+    ]
+}
+
+/// Color of a [`Gradient`] at `position`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Stop {
+    /// Position in `0..=1`.
+    pub position: f64,
+    /// Red, green and blue sRGB values.
+    pub color: [u8; 3],
+}
+
+impl Stop {
+    pub fn new(position: f64, RGBColor(r, g, b): RGBColor) -> Self {
+        Stop {
+            position,
+            color: [r, g, b],
+        }
+    }
+
+    fn rgb(&self) -> RGBColor {
+        let [r, g, b] = self.color;
+        RGBColor(r, g, b)
+    }
+}
+
+/// Colors of one run through a [`Palette`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Gradient {
+    /// One sweep through the hue circle at full saturation.
+    #[default]
+    Hue,
+    /// Matplotlib's perceptually uniform blue to green to yellow map.
+    Viridis,
+    /// Matplotlib's perceptually uniform black to purple to cream map.
+    Magma,
+    /// Matplotlib's perceptually uniform black to red to pale yellow map.
+    Inferno,
+    /// Matplotlib's perceptually uniform blue to magenta to yellow map.
+    Plasma,
+    /// Colors mixed between stops sorted by position. Positions before the
+    /// first and after the last stop take their color.
+    Stops { stops: Vec<Stop> },
+}
+
+/// Built-in maps sampled at nine evenly spaced positions.
+const VIRIDIS: [u32; 9] = [
+    0x440154, 0x472c7a, 0x3b518b, 0x2c718e, 0x21908d, 0x27ad81, 0x5cc863, 0xaadc32, 0xfde725,
+];
+const MAGMA: [u32; 9] = [
+    0x000004, 0x1c1044, 0x4f127b, 0x812581, 0xb5367a, 0xe55064, 0xfb8761, 0xfec287, 0xfcfdbf,
+];
+const INFERNO: [u32; 9] = [
+    0x000004, 0x1f0c48, 0x550f6d, 0x88226a, 0xba3655, 0xe35933, 0xf98c0a, 0xf9c932, 0xfcffa4,
+];
+const PLASMA: [u32; 9] = [
+    0x0d0887, 0x4c02a1, 0x7e03a8, 0xa92395, 0xcc4778, 0xe56b5d, 0xf89441, 0xfdc328, 0xf0f921,
+];
+
+fn hex(rgb: u32) -> RGBColor {
+    RGBColor((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+}
+
+impl Gradient {
+    /// Color at position `t` in `0..=1`, mixing colors in `space`.
+    pub fn color(&self, t: f64, space: ColorSpace) -> RGBColor {
+        let even = |colors: &[u32]| {
+            let x = t.clamp(0., 1.) * (colors.len() - 1) as f64;
+            let i = (x as usize).min(colors.len() - 2);
+            space.mix(hex(colors[i]), hex(colors[i + 1]), x - i as f64)
+        };
+
+        match self {
+            Gradient::Hue => {
+                let (r, g, b) = HSLColor(t, 1.0, 0.5).rgb();
+                RGBColor(r, g, b)
+            }
+            Gradient::Viridis => even(&VIRIDIS),
+            Gradient::Magma => even(&MAGMA),
+            Gradient::Inferno => even(&INFERNO),
+            Gradient::Plasma => even(&PLASMA),
+            Gradient::Stops { stops } => {
+                let next = stops.partition_point(|stop| stop.position <= t);
+                match (next.checked_sub(1), stops.get(next)) {
+                    (Some(i), Some(b)) => {
+                        let a = &stops[i];
+                        let f = (t - a.position) / (b.position - a.position);
+                        space.mix(a.rgb(), b.rgb(), f)
+                    }
+                    (Some(i), None) => stops[i].rgb(),
+                    (None, _) => stops[0].rgb(),
+                }
+            }
+        }
+    }
+
+    /// Checks that there is at least one stop and that stops are sorted.
+    pub fn validate(&self) -> Result<(), String> {
+        let Gradient::Stops { stops } = self else {
+            return Ok(());
+        };
+        if stops.is_empty() {
+            return Err("gradient has no stops".to_string());
+        }
+        if let Some(stop) = stops
+            .iter()
+            .find(|stop| !(0. ..=1.).contains(&stop.position))
+        {
+            return Err(format!(
+                "gradient stop position {} is not between 0 and 1",
+                stop.position
+            ));
+        }
+        if stops
+            .windows(2)
+            .any(|pair| pair[0].position > pair[1].position)
+        {
+            return Err("gradient stops are not sorted by position".to_string());
+        }
+        Ok(())
+    }
+
+    /// Reads a gradient file, a Fractint `.map` or GIMP `.ggr` file judged by
+    /// its extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Gradient, GradientError> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        let parse = match extension.as_deref() {
+            Some("map") => Gradient::parse_map,
+            Some("ggr") => Gradient::parse_ggr,
+            _ => return Err(GradientError::UnknownFormat(path.to_owned())),
+        };
+        parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parses a Fractint color map: one color per line given as red, green
+    /// and blue values separated by whitespace, optionally followed by a
+    /// comment. The colors are spread evenly over the gradient.
+    pub fn parse_map(source: &str) -> Result<Gradient, GradientError> {
+        let colors = source
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(number, line)| {
+                let mut values = line.split_whitespace().map(str::parse::<u8>);
+                match [values.next(), values.next(), values.next()] {
+                    [Some(Ok(r)), Some(Ok(g)), Some(Ok(b))] => Ok(RGBColor(r, g, b)),
+                    _ => Err(GradientError::Parse(format!(
+                        "line {} doesn't start with three values from 0 to 255",
+                        number + 1
+                    ))),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if colors.is_empty() {
+            return Err(GradientError::Parse("color map has no colors".to_string()));
+        }
+
+        let last = (colors.len() - 1).max(1) as f64;
+        let stops = (0..)
+            .zip(colors)
+            .map(|(i, color)| Stop::new(i as f64 / last, color))
+            .collect();
+        Ok(Gradient::Stops { stops })
+    }
+
+    /// Parses a GIMP gradient. Every segment is turned into enough stops to
+    /// follow its blending function; transparency is ignored.
+    pub fn parse_ggr(source: &str) -> Result<Gradient, GradientError> {
+        let error = |message: &str| GradientError::Parse(message.to_string());
+
+        let mut lines = source
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty());
+        if lines.next() != Some("GIMP Gradient") {
+            return Err(error("missing \"GIMP Gradient\" header"));
+        }
+        let mut count = lines.next();
+        if count.is_some_and(|line| line.starts_with("Name:")) {
+            count = lines.next();
+        }
+        let count: usize = count
+            .and_then(|line| line.parse().ok())
+            .ok_or_else(|| error("missing number of segments"))?;
+
+        let segments = lines
+            .take(count)
+            .map(GgrSegment::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        if segments.len() != count {
+            return Err(error("fewer segments than announced"));
+        }
+        if segments.is_empty() {
+            return Err(error("gradient has no segments"));
+        }
+
+        let stops = segments.iter().flat_map(GgrSegment::stops).collect();
+        let gradient = Gradient::Stops { stops };
+        gradient.validate().map_err(GradientError::Parse)?;
+        Ok(gradient)
+    }
+}
+
+/// Number of stops approximating a smoothly blended GIMP gradient segment.
+const GGR_SEGMENT_STOPS: usize = 16;
+
+/// Segment of a GIMP gradient.
+struct GgrSegment {
+    left: f64,
+    middle: f64,
+    right: f64,
+    left_color: [f64; 3],
+    right_color: [f64; 3],
+    /// 0 linear, 1 curved, 2 sine, 3 sphere increasing, 4 sphere decreasing,
+    /// 5 step.
+    blend: u32,
+    /// 0 RGB, 1 HSV counter-clockwise, 2 HSV clockwise.
+    coloring: u32,
+}
+
+impl GgrSegment {
+    fn parse(line: &str) -> Result<Self, GradientError> {
+        let values = line
+            .split_whitespace()
+            .map(str::parse::<f64>)
+            .collect::<Result<Vec<_>, _>>()
+            .ok()
+            .filter(|values| values.len() >= 13)
+            .ok_or_else(|| GradientError::Parse(format!("malformed segment {:?}", line)))?;
+
+        Ok(GgrSegment {
+            left: values[0],
+            middle: values[1],
+            right: values[2],
+            left_color: [values[3], values[4], values[5]],
+            right_color: [values[7], values[8], values[9]],
+            blend: values[11] as u32,
+            coloring: values[12] as u32,
+        })
+    }
+
+    fn stops(&self) -> Vec<Stop> {
+        let width = self.right - self.left;
+        let middle = if width > 0. {
+            (self.middle - self.left) / width
+        } else {
+            0.5
+        };
+
+        if self.blend == 5 {
+            let (left, right) = (self.mix(0.), self.mix(1.));
+            return vec![
+                Stop::new(self.left, left),
+                Stop::new(self.middle, left),
+                Stop::new(self.middle, right),
+                Stop::new(self.right, right),
+            ];
+        }
+
+        (0..=GGR_SEGMENT_STOPS)
+            .map(|i| {
+                let x = i as f64 / GGR_SEGMENT_STOPS as f64;
+                Stop::new(self.left + x * width, self.mix(self.factor(x, middle)))
+            })
+            .collect()
+    }
+
+    /// Blending factor at the relative position `x` of the segment, GIMP's
+    /// definition.
+    fn factor(&self, x: f64, middle: f64) -> f64 {
+        let linear = if x <= middle {
+            if middle > 0. {
+                0.5 * x / middle
+            } else {
+                0.
+            }
+        } else if middle < 1. {
+            0.5 + 0.5 * (x - middle) / (1. - middle)
+        } else {
+            1.
+        };
+
+        match self.blend {
+            1 => x.powf(0.5f64.ln() / middle.clamp(1e-10, 1. - 1e-10).ln()),
+            2 => ((linear - 0.5) * PI).sin() / 2. + 0.5,
+            3 => (1. - (linear - 1.).powi(2)).sqrt(),
+            4 => 1. - (1. - linear.powi(2)).sqrt(),
+            _ => linear,
+        }
+    }
+
+    /// Color a fraction `f` of the way from the left to the right color.
+    fn mix(&self, f: f64) -> RGBColor {
+        let channel = |value: f64| (value.clamp(0., 1.) * 255.).round() as u8;
+        let [r, g, b] = match self.coloring {
+            direction @ (1 | 2) => {
+                let [h0, s0, v0] = rgb_to_hsv(self.left_color);
+                let [h1, s1, v1] = rgb_to_hsv(self.right_color);
+                let mut dh = h1 - h0;
+                if direction == 1 && dh < 0. {
+                    dh += 1.;
+                } else if direction == 2 && dh > 0. {
+                    dh -= 1.;
+                }
+                let h = (h0 + f * dh).rem_euclid(1.);
+                hsv_to_rgb([h, s0 + f * (s1 - s0), v0 + f * (v1 - v0)])
+            }
+            _ => lerp(self.left_color, self.right_color, f),
+        };
+        RGBColor(channel(r), channel(g), channel(b))
+    }
+}
+
+fn rgb_to_hsv([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let max = r.max(g).max(b);
+    let range = max - r.min(g).min(b);
+    let hue = if range == 0. {
+        0.
+    } else if max == r {
+        ((g - b) / range).rem_euclid(6.)
+    } else if max == g {
+        (b - r) / range + 2.
+    } else {
+        (r - g) / range + 4.
+    };
+    let saturation = if max > 0. { range / max } else { 0. };
+    [hue / 6., saturation, max]
+}
+
+fn hsv_to_rgb([h, s, v]: [f64; 3]) -> [f64; 3] {
+    let channel = |n: f64| {
+        let k = (n + h * 6.) % 6.;
+        v - v * s * k.min(4. - k).clamp(0., 1.)
+    };
+    [channel(5.), channel(3.), channel(1.)]
+}
+
+/// Reasons a gradient file can't be loaded.
+#[derive(Debug)]
+pub enum GradientError {
+    /// The gradient file couldn't be read.
+    Io(io::Error),
+    /// The gradient file has neither a `.map` nor a `.ggr` extension.
+    UnknownFormat(PathBuf),
+    /// The gradient file isn't well-formed.
+    Parse(String),
+}
+
+impl fmt::Display for GradientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GradientError::Io(err) => write!(f, "can't read gradient: {}", err),
+            GradientError::UnknownFormat(path) => write!(
+                f,
+                "can't tell the format of gradient {}, expected a .map or .ggr file",
+                path.display()
+            ),
+            GradientError::Parse(message) => write!(f, "malformed gradient: {}", message),
+        }
+    }
+}
+
+impl Error for GradientError {}
+
+impl From<io::Error> for GradientError {
+    fn from(err: io::Error) -> Self {
+        GradientError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: RGBColor = RGBColor(255, 0, 0);
+    const BLUE: RGBColor = RGBColor(0, 0, 255);
+
+    fn red_to_blue() -> Gradient {
+        Gradient::Stops {
+            stops: vec![Stop::new(0.25, RED), Stop::new(0.75, BLUE)],
+        }
+    }
+
+    #[test]
+    fn stops_test() {
+        let gradient = red_to_blue();
+        assert_eq!(gradient.color(0., ColorSpace::Srgb), RED);
+        assert_eq!(gradient.color(0.5, ColorSpace::Srgb), RGBColor(128, 0, 128));
+        assert_eq!(gradient.color(1., ColorSpace::Srgb), BLUE);
+
+        assert_eq!(
+            gradient.color(0.5, ColorSpace::LinearRgb),
+            RGBColor(188, 0, 188)
+        );
+        let RGBColor(r, g, b) = gradient.color(0.5, ColorSpace::Oklab);
+        assert!(r > 128 && b > 128 && g < r);
+    }
+
+    #[test]
+    fn color_space_test() {
+        //  Converting back and forth keeps every color
+        for value in [0, 1, 10, 100, 128, 254, 255] {
+            let color = RGBColor(value, 255 - value, value / 2);
+            for space in [ColorSpace::Srgb, ColorSpace::LinearRgb, ColorSpace::Oklab] {
+                assert_eq!(space.mix(color, BLUE, 0.), color, "{:?}", space);
+                assert_eq!(space.mix(RED, color, 1.), color, "{:?}", space);
+            }
+        }
+    }
+
+    #[test]
+    fn average_test() {
+        //  Half black, half white is half as bright, not a middle sRGB value
+        assert_eq!(
+            average([RGBColor(0, 0, 0), RGBColor(255, 255, 255)]),
+            RGBColor(188, 188, 188)
+        );
+        assert_eq!(average([RED; 3]), RED);
+        assert_eq!(average([RED, BLUE]), RGBColor(188, 0, 188));
+    }
+
+    #[test]
+    fn repeat_test() {
+        let palette = Palette::new(Gradient::Viridis);
+        assert_eq!(palette.color(0.), hex(VIRIDIS[0]));
+        assert_eq!(palette.color(1.), hex(VIRIDIS[8]));
+
+        let palette = Palette {
+            repeat: 2.,
+            offset: 0.25,
+            ..Palette::new(red_to_blue())
+        };
+        assert_eq!(palette.color(0.), RED);
+        assert_eq!(palette.color(0.125), RGBColor(128, 0, 128));
+        assert_eq!(palette.color(0.375), BLUE);
+        assert_eq!(palette.color(0.5), RED);
+    }
+
+    #[test]
+    fn validate_test() {
+        assert!(Palette::default().validate().is_ok());
+        assert!(Palette::new(red_to_blue()).validate().is_ok());
+
+        let invalid = [
+            Palette {
+                repeat: 0.,
+                ..Palette::default()
+            },
+            Palette {
+                offset: f64::NAN,
+                ..Palette::default()
+            },
+            Palette::new(Gradient::Stops { stops: vec![] }),
+            Palette::new(Gradient::Stops {
+                stops: vec![Stop::new(0.75, RED), Stop::new(0.25, BLUE)],
+            }),
+            Palette::new(Gradient::Stops {
+                stops: vec![Stop::new(1.5, RED)],
+            }),
+        ];
+        for palette in invalid {
+            assert!(palette.validate().is_err(), "{:?}", palette);
+        }
+    }
+
+    #[test]
+    fn map_test() {
+        let gradient = Gradient::parse_map("255 0 0 red\n\n0 255 0\n  0 0 255 ; blue\n").unwrap();
+        assert_eq!(
+            gradient,
+            Gradient::Stops {
+                stops: vec![
+                    Stop::new(0., RED),
+                    Stop::new(0.5, RGBColor(0, 255, 0)),
+                    Stop::new(1., BLUE),
+                ]
+            }
+        );
+
+        assert!(Gradient::parse_map("").is_err());
+        assert!(Gradient::parse_map("255 0\n").is_err());
+        assert!(Gradient::parse_map("256 0 0\n").is_err());
+    }
+
+    #[test]
+    fn ggr_test() {
+        let source = "GIMP Gradient\nName: Test\n2\n\
+            0 0.25 0.5 1 0 0 1 0 0 1 1 0 0\n\
+            0.5 0.75 1 0 1 0 1 0 0 1 1 5 0\n";
+        let gradient = Gradient::parse_ggr(source).unwrap();
+        let color = |t| gradient.color(t, ColorSpace::Srgb);
+
+        //  Linear red to blue, then a step from green to blue
+        assert_eq!(color(0.), RED);
+        assert_eq!(color(0.25), RGBColor(128, 0, 128));
+        assert_eq!(color(0.5), RGBColor(0, 255, 0));
+        assert_eq!(color(0.7), RGBColor(0, 255, 0));
+        assert_eq!(color(0.8), BLUE);
+
+        //  Counter-clockwise HSV blending from red passes through green
+        let hsv = "GIMP Gradient\n1\n0 0.5 1 1 0 0 1 0 0 1 1 0 1\n";
+        let gradient = Gradient::parse_ggr(hsv).unwrap();
+        assert_eq!(gradient.color(0.5, ColorSpace::Srgb), RGBColor(0, 255, 0));
+
+        assert!(Gradient::parse_ggr("GIMP Gradient\n2\n0 0.5 1 1 0 0 1 0 0 1 1 0 0\n").is_err());
+        assert!(Gradient::parse_ggr("0 0.5 1 1 0 0 1 0 0 1 1 0 0\n").is_err());
+    }
+}
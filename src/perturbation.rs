@@ -0,0 +1,304 @@
+//! Perturbation rendering of deep zooms into the Mandelbrot set.
+//!
+//! Past zooms of about $10^{13}$, neighbouring pixels are the same `f64`
+//! number and the image falls apart into blocks. Instead, the orbit $Z_n$ of
+//! the center of the view is computed once with [`Fixed`] numbers as precise
+//! as the pixels are small, the *reference orbit*. The orbit of a pixel
+//! $C + \delta c$ is then only tracked as its difference
+//! $\delta_n = z_n - Z_n$ from the reference, which obeys
+//! $$\delta_{n+1} = 2 Z_n \delta_n + \delta_n^2 + \delta c$$
+//! and can be iterated in `f64`, as long as pixels stay above its smallest
+//! normal numbers, around zooms of $10^{300}$.
+//!
+//! Pixels glitch when their orbit comes closer to `0` than to the reference,
+//! $|z_n| < |\delta_n|$, as the small `z_n` is then the difference of two much
+//! larger numbers. Such pixels, and those that outlive the reference orbit,
+//! are rebased: the difference becomes $\delta_n = z_n$ and the pixel goes on
+//! along the reference orbit from its start $Z_0 = 0$.
+
+use crate::average::OrbitAverage;
+use crate::bignum::Fixed;
+use crate::escape::{ComplexDouble, EscapeResult, Status, Track, DEFAULT_ESCAPE_RADIUS};
+use crate::render::{render_with, Image, Settings};
+use crate::trap::TrapHit;
+use crate::view::View;
+
+/// Bits of precision of the reference orbit beyond those needed to tell the
+/// pixels apart.
+pub const PRECISION_MARGIN: u32 = 64;
+
+/// Bits after the binary point needed to compute the reference orbit of
+/// `view` sampled with `samples` pixels.
+pub fn precision(view: &View, samples: (u32, u32)) -> u32 {
+    let step = view.step(samples);
+    (-step.0.min(step.1).log2()).max(0.).ceil() as u32 + PRECISION_MARGIN
+}
+
+/// Orbit of a reference point of the Mandelbrot set, rounded to `f64`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReferenceOrbit {
+    /// Orbit points from $Z_0 = 0$, up to the first one outside the circle of
+    /// radius 2 if the orbit escapes.
+    points: Vec<ComplexDouble>,
+}
+
+impl ReferenceOrbit {
+    /// Orbit of the point `re + i im` over `num_iterations` steps, computed
+    /// with the precision of `re`.
+    pub fn new(re: &Fixed, im: &Fixed, num_iterations: u32) -> Self {
+        let mut z = (Fixed::zero(re.precision()), Fixed::zero(re.precision()));
+        let mut points = vec![ComplexDouble::new(0., 0.)];
+        while points.len() <= num_iterations as usize {
+            let square = (&z.0 * &z.0, &z.1 * &z.1, &z.0 * &z.1);
+            z = (&(&square.0 - &square.1) + re, &(&square.2 + &square.2) + im);
+
+            let point = ComplexDouble::new(z.0.to_f64(), z.1.to_f64());
+            points.push(point);
+            if point.norm() > DEFAULT_ESCAPE_RADIUS {
+                break;
+            }
+        }
+        ReferenceOrbit { points }
+    }
+
+    /// Reference orbit of the center of `view` sampled with `samples`
+    /// pixels.
+    ///
+    /// # Panics
+    ///
+    /// If the center is further from the origin than [`View::validate`]
+    /// allows.
+    pub fn centered(view: &View, samples: (u32, u32), num_iterations: u32) -> Self {
+        let precision = precision(view, samples);
+        let fixed = |x| Fixed::from_decimal(x, precision).expect("view center in range");
+        ReferenceOrbit::new(
+            &fixed(&view.center.re),
+            &fixed(&view.center.im),
+            num_iterations,
+        )
+    }
+
+    /// Number of iterations the reference orbit lasts.
+    pub fn len(&self) -> usize {
+        self.points.len() - 1
+    }
+
+    /// Whether the reference point escapes right away.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Same as [`escape_with`](crate::escape::escape_with) for the pixel
+    /// `dc` away from the reference point.
+    ///
+    /// Since the pixel's orbit is only known relative to the reference, there
+    /// are no early-outs for bounded orbits and no attracting cycles.
+    pub fn escape(
+        &self,
+        dc: ComplexDouble,
+        num_iterations: u32,
+        escape_radius: f64,
+        track: Track,
+    ) -> EscapeResult {
+        let (mut m, mut delta) = (0, ComplexDouble::new(0., 0.));
+        let mut z = delta;
+        let mut derivative = track.derivative.then_some(ComplexDouble::new(0., 0.));
+        let mut min_distance = track.min_distance.then_some(f64::INFINITY);
+        let mut trap = track.trap.map(|_| TrapHit::default());
+        let mut average = track.average.map(|_| OrbitAverage::default());
+        //  The reference orbit starts with 0 and the center of the view
+        let c = self.points.get(1).copied().unwrap_or_default() + dc;
+
+        for count in 0..=num_iterations {
+            if z.norm() > escape_radius {
+                return EscapeResult {
+                    status: Status::Escaped,
+                    count,
+                    z,
+                    derivative,
+                    min_distance,
+                    cycle: None,
+                    trap,
+                    average,
+                };
+            }
+            if count == num_iterations {
+                break;
+            }
+
+            derivative = derivative.map(|dz| 2. * z * dz + 1.);
+            delta = 2. * self.points[m] * delta + delta * delta + dc;
+            m += 1;
+            z = self.points[m] + delta;
+            min_distance = min_distance.map(|distance| distance.min(z.norm()));
+            trap = trap
+                .zip(track.trap.as_ref())
+                .map(|(hit, shape)| hit.update(shape, z));
+            average = average
+                .zip(track.average.as_ref())
+                .map(|(average, statistic)| average.update(statistic, z, c));
+
+            if z.norm_sqr() < delta.norm_sqr() || m == self.len() {
+                delta = z;
+                m = 0;
+            }
+        }
+
+        EscapeResult {
+            status: Status::Bounded,
+            count: num_iterations,
+            z,
+            derivative,
+            min_distance,
+            cycle: None,
+            trap,
+            average,
+        }
+    }
+}
+
+/// Colors of a grid of `samples` pixels showing the Mandelbrot set, computed
+/// relative to the reference orbit of the center of the view.
+pub fn render_perturbed(settings: &Settings, samples: (u32, u32)) -> Image {
+    let reference = ReferenceOrbit::centered(&settings.view, samples, settings.iterations);
+    let bailout = settings.bailout();
+    let track = settings.track();
+    render_with(settings, samples, 2., |x, y| {
+        let dc = settings.view.offset(x, y, samples);
+        reference.escape(dc, settings.iterations, bailout, track)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bignum::{ComplexDecimal, Decimal};
+    use crate::double_double::{ComplexDoubleDouble, DoubleDouble};
+    use crate::escape::{escape_with, mandelbrot};
+    use crate::fractal::Mandelbrot;
+
+    /// Escape count of `re + i im` iterated entirely with [`Fixed`] numbers.
+    fn exact_count(re: &Fixed, im: &Fixed, num_iterations: u32) -> u32 {
+        let reference = ReferenceOrbit::new(re, im, num_iterations);
+        match reference.points.last() {
+            Some(z) if z.norm() > DEFAULT_ESCAPE_RADIUS => reference.len() as u32,
+            _ => num_iterations,
+        }
+    }
+
+    #[test]
+    fn shallow_test() {
+        //  Where f64 is precise enough, perturbation gives the same counts
+        let view = View::centered(ComplexDouble::new(-0.745, 0.1), 100., 1.);
+        let samples = (40, 40);
+        let reference = ReferenceOrbit::centered(&view, samples, 500);
+        let mut differences = 0;
+        for (x, y) in (0..40).flat_map(|x| (0..40).map(move |y| (x, y))) {
+            let direct = escape_with(
+                &Mandelbrot,
+                &view.point(x, y, samples),
+                500,
+                2.,
+                Track {
+                    final_z: true,
+                    ..Track::default()
+                },
+            );
+            let perturbed = reference.escape(
+                view.offset(x as f64 + 0.5, y as f64 + 0.5, samples),
+                500,
+                2.,
+                Track::default(),
+            );
+            assert_eq!(direct.escaped(), perturbed.escaped());
+            differences += (direct.count != perturbed.count) as u32;
+        }
+        assert!(differences <= 16, "{}", differences);
+    }
+
+    #[test]
+    fn rebase_test() {
+        //  A reference that escapes right away still serves pixels in the set
+        let reference =
+            ReferenceOrbit::new(&Fixed::from_f64(0.5, 64), &Fixed::from_f64(0., 64), 100);
+        assert_eq!(reference.len(), 5);
+        for dc in [-0.5, -1.5, -1.75, -2.6] {
+            let c = ComplexDouble::new(0.5 + dc, 0.);
+            let direct = escape_with(
+                &Mandelbrot,
+                &c,
+                100,
+                2.,
+                Track {
+                    final_z: true,
+                    ..Track::default()
+                },
+            );
+            let perturbed = reference.escape(ComplexDouble::new(dc, 0.), 100, 2., Track::default());
+            assert_eq!(
+                (direct.status, direct.count),
+                (perturbed.status, perturbed.count)
+            );
+        }
+    }
+
+    #[test]
+    fn deep_test() {
+        //  Pixels 1e-39 apart around the tip at i, checked against exact
+        //  iteration
+        let (re, im) = (0., 1.);
+        let radius = 1e-38;
+        let view = View {
+            center: ComplexDecimal::new(Decimal::from_f64(re), Decimal::from_f64(im)),
+            radius: Decimal::from_f64(radius),
+            aspect: 1.,
+        };
+        let samples = (20, 20);
+        let precision = precision(&view, samples);
+        assert!(precision >= 190);
+
+        let reference = ReferenceOrbit::centered(&view, samples, 3000);
+        let (center_re, center_im) = (
+            Fixed::from_f64(re, precision),
+            Fixed::from_f64(im, precision),
+        );
+        let mut counts = Vec::new();
+        for (x, y) in [(0, 0), (19, 0), (7, 13), (10, 10), (3, 18)] {
+            let dc = view.offset(x as f64, y as f64, samples);
+            let exact = exact_count(
+                &(&center_re + &Fixed::from_f64(dc.re, precision)),
+                &(&center_im + &Fixed::from_f64(dc.im, precision)),
+                3000,
+            );
+            let perturbed = reference.escape(dc, 3000, 2., Track::default());
+            assert_eq!(perturbed.count, exact, "{:?}", (x, y));
+            counts.push(exact);
+        }
+        //  Unlike plain f64, the pixels aren't all the same
+        counts.dedup();
+        assert!(counts.len() > 1, "{:?}", counts);
+    }
+
+    #[test]
+    fn double_double_test() {
+        //  Points up to 1e-23 above the tip at i all land on it in f64, but
+        //  escape at different counts
+        let mut counts = Vec::new();
+        for offset in [1e-23, 1e-25, -1e-27] {
+            let im = DoubleDouble::new(1.) + DoubleDouble::new(offset);
+            let c = ComplexDoubleDouble::new(DoubleDouble::new(0.), im);
+            let count = mandelbrot(&c, 500);
+            assert_eq!(mandelbrot(&ComplexDouble::new(0., im.to_f64()), 500), 500);
+
+            let exact = exact_count(
+                &Fixed::zero(200),
+                &(&Fixed::from_f64(1., 200) + &Fixed::from_f64(offset, 200)),
+                500,
+            );
+            assert_eq!(count, exact);
+            counts.push(count);
+        }
+        counts.dedup();
+        assert_eq!(counts.len(), 3);
+    }
+}
@@ -0,0 +1,163 @@
+//! Floating-point precision the Mandelbrot set is iterated with.
+//!
+//! The generic [`mandelbrot_with`](crate::escape::mandelbrot_with) kernel and
+//! [`View::sample_as`](crate::view::View::sample_as) work with any [`Real`]
+//! type: `f32` for quick previews, `f64`, or [`DoubleDouble`] for zooms past
+//! what `f64` resolves. [`Precision`] selects one of them for a render.
+
+use std::fmt::Debug;
+
+use num::Num;
+use serde::{Deserialize, Serialize};
+
+use crate::bignum::{Decimal, Fixed};
+use crate::double_double::DoubleDouble;
+use crate::view::View;
+
+/// Real number type points can be iterated with.
+pub trait Real: Num + Copy + PartialOrd + Debug + Send + Sync {
+    /// Bits of mantissa, including the implicit leading one.
+    const MANTISSA_BITS: u32;
+
+    /// Closest number to `x`.
+    fn from_f64(x: f64) -> Self;
+
+    /// Closest `f64`.
+    fn to_f64(self) -> f64;
+
+    /// Closest number to `x`.
+    fn from_decimal(x: &Decimal) -> Self {
+        Self::from_f64(x.to_f64())
+    }
+}
+
+impl Real for f32 {
+    const MANTISSA_BITS: u32 = f32::MANTISSA_DIGITS;
+
+    fn from_f64(x: f64) -> Self {
+        x as f32
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Real for f64 {
+    const MANTISSA_BITS: u32 = f64::MANTISSA_DIGITS;
+
+    fn from_f64(x: f64) -> Self {
+        x
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+impl Real for DoubleDouble {
+    const MANTISSA_BITS: u32 = 2 * f64::MANTISSA_DIGITS;
+
+    fn from_f64(x: f64) -> Self {
+        DoubleDouble::new(x)
+    }
+
+    fn to_f64(self) -> f64 {
+        DoubleDouble::to_f64(self)
+    }
+
+    /// Through a [`Fixed`] number with bits to spare below those of `hi`
+    /// and `lo`, or through `f64` beyond the range of those.
+    fn from_decimal(x: &Decimal) -> Self {
+        const PRECISION: u32 = 192;
+        let Some(fixed) = Fixed::from_decimal(x, PRECISION) else {
+            return DoubleDouble::new(x.to_f64());
+        };
+        let hi = fixed.to_f64();
+        let lo = (&fixed - &Fixed::from_f64(hi, PRECISION)).to_f64();
+        DoubleDouble::new(hi) + DoubleDouble::new(lo)
+    }
+}
+
+/// Bits of mantissa [`Precision::Auto`] keeps below the size of a pixel, so
+/// that rounding errors grow for a while before neighbouring pixels blur.
+pub const GUARD_BITS: u32 = 12;
+
+/// Number type a render iterates points with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Precision {
+    /// The cheapest of `f64` and double-double that resolves the pixels.
+    #[default]
+    Auto,
+    /// `f32`, fast but only good for views larger than about `1e-4`.
+    F32,
+    /// `f64`, good for views larger than about `1e-12`.
+    F64,
+    /// [`DoubleDouble`], good for views larger than about `1e-28`.
+    DoubleDouble,
+}
+
+impl Precision {
+    /// Precision `view` sampled with `samples` pixels is rendered with,
+    /// i.e. the one selected by [`Precision::Auto`] or `self` otherwise.
+    pub fn resolve(self, view: &View, samples: (u32, u32)) -> Precision {
+        if self != Precision::Auto {
+            return self;
+        }
+        match bits_needed(view, samples) <= f64::MANTISSA_BITS {
+            true => Precision::F64,
+            false => Precision::DoubleDouble,
+        }
+    }
+}
+
+/// Bits of mantissa needed to tell apart the pixels of `view` sampled with
+/// `samples` pixels, with [`GUARD_BITS`] to spare.
+pub fn bits_needed(view: &View, samples: (u32, u32)) -> u32 {
+    let re = view.re();
+    let im = view.im();
+    let largest = [re.start, re.end, im.start, im.end]
+        .into_iter()
+        .fold(0., |largest: f64, x| largest.max(x.abs()));
+    let step = view.step(samples);
+    (largest / step.0.min(step.1)).log2().max(0.).ceil() as u32 + GUARD_BITS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::escape::ComplexDouble;
+
+    #[test]
+    fn from_decimal_test() {
+        let x: Decimal = "0.1000000000000000000000000000001".parse().unwrap();
+        assert_eq!(f64::from_decimal(&x), 0.1);
+        let double_double = DoubleDouble::from_decimal(&x);
+        let tenth = DoubleDouble::new(1.) / DoubleDouble::new(10.);
+        assert!(((double_double - tenth).to_f64() - 1e-31).abs() < 1e-33);
+
+        let huge: Decimal = "-1e30".parse().unwrap();
+        assert_eq!(DoubleDouble::from_decimal(&huge), DoubleDouble::new(-1e30));
+    }
+
+    #[test]
+    fn resolve_test() {
+        let samples = (1000, 1000);
+        let zoomed = |zoom| View::centered(ComplexDouble::new(-0.75, 0.1), zoom, 1.);
+
+        assert_eq!(bits_needed(&View::default(), samples), 22);
+        assert_eq!(
+            Precision::Auto.resolve(&zoomed(1e9), samples),
+            Precision::F64
+        );
+        assert_eq!(
+            Precision::Auto.resolve(&zoomed(1e12), samples),
+            Precision::DoubleDouble
+        );
+        assert_eq!(
+            Precision::F32.resolve(&zoomed(1e12), samples),
+            Precision::F32
+        );
+    }
+}
@@ -0,0 +1,782 @@
+//! Rendering of the set onto a plotters drawing area.
+//!
+//! Rendering happens in three steps that can also be run one by one:
+//! [`iterate`] fills a [`Buffer`] with the [`EscapeResult`] of one sample per
+//! pixel, [`shade`] turns it into colors, taking extra samples per pixel when
+//! supersampling, and [`blit`] copies the colors onto a drawing area.
+
+use std::error::Error;
+use std::path::Path;
+
+use num::complex::Complex;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::buffer::Buffer;
+use crate::color::{ColorContext, Coloring, InteriorColoring};
+use crate::double_double::DoubleDouble;
+use crate::escape::{escape_with, mandelbrot_with, EscapeResult, Track, DEFAULT_ESCAPE_RADIUS};
+use crate::fractal::{BurningShip, Fractal, FractalKind, Julia, Mandelbrot, Multibrot, Tricorn};
+use crate::lighting::Lighting;
+use crate::palette::{average, to_linear, Palette};
+use crate::perturbation::render_perturbed;
+use crate::precision::{Precision, Real};
+use crate::sampling::SamplePattern;
+use crate::simd::{self, render_lanes};
+use crate::subdivide::{iterate_subdivided, subdivide_with};
+use crate::trap::OrbitTrap;
+use crate::view::View;
+
+/// Largest number of samples per pixel along each axis.
+pub const MAX_SUPERSAMPLING: u32 = 16;
+
+/// Parameters of a single render.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Fractal to draw.
+    pub fractal: FractalKind,
+    /// Window of the complex plane to draw.
+    pub view: View,
+    /// Canvas size in pixels, including the axis margins.
+    pub size: (u32, u32),
+    /// Maximum number of iterations per point.
+    pub iterations: u32,
+    /// Radius of the circle an orbit has to leave to count as escaped. It is
+    /// raised to what the coloring needs, see [`Settings::bailout`].
+    pub escape_radius: f64,
+    /// How escape counts are mapped onto the palette.
+    pub coloring: Coloring,
+    /// How the points that never escape are colored.
+    pub interior: InteriorColoring,
+    /// Colors of the escaping points.
+    pub palette: Palette,
+    /// Number of samples per pixel along each axis; the colors of the
+    /// `supersampling²` samples are averaged.
+    pub supersampling: u32,
+    /// Layout of the samples inside a supersampled pixel.
+    pub sample_pattern: SamplePattern,
+    /// With a threshold, only pixels that differ from one of their
+    /// neighbours by more than it are supersampled: in a linear-light color
+    /// channel, in `0..=1`, or in their position along the palette, in runs
+    /// through the gradient. Pixels on the boundary of the set always are.
+    pub adaptive_threshold: Option<f64>,
+    /// Orbit trap coloring escaping and bounded points instead of `coloring`
+    /// and `interior`, which only color the points that miss image traps.
+    pub trap: Option<OrbitTrap>,
+    /// Light shading escaping points as a relief on top of their color, for
+    /// fractals with a derivative.
+    pub lighting: Option<Lighting>,
+    /// Whether uniform rectangles are filled without iterating their inside,
+    /// see [`subdivide`](crate::subdivide). Not available with
+    /// perturbation, which can't tell points inside the set.
+    pub subdivide: bool,
+    /// Whether the Mandelbrot set is iterated relative to a high-precision
+    /// orbit of the view center, see [`perturbation`](crate::perturbation),
+    /// which is needed for zooms beyond about `1e13`.
+    pub perturbation: bool,
+    /// Number type the Mandelbrot set is iterated with, unless rendered by
+    /// perturbation. Other fractals are always iterated with `f64`.
+    pub precision: Precision,
+    /// Whether the Mandelbrot set is iterated several points at a time with
+    /// the vector instructions of the CPU, see [`simd`](crate::simd). Only
+    /// available with `f64`, without perturbation or subdivision, and with
+    /// colorings that only need escape counts and last points.
+    pub simd: bool,
+    /// Number of threads computing pixels, `0` for one per available core.
+    /// The image doesn't depend on it, so it isn't part of scenes.
+    #[serde(skip)]
+    pub threads: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            fractal: FractalKind::default(),
+            view: View::default(),
+            size: (1600, 1200),
+            iterations: 100,
+            escape_radius: DEFAULT_ESCAPE_RADIUS,
+            coloring: Coloring::default(),
+            interior: InteriorColoring::default(),
+            palette: Palette::default(),
+            supersampling: 1,
+            sample_pattern: SamplePattern::default(),
+            adaptive_threshold: None,
+            trap: None,
+            lighting: None,
+            subdivide: false,
+            perturbation: false,
+            precision: Precision::default(),
+            simd: false,
+            threads: 0,
+        }
+    }
+}
+
+impl Settings {
+    /// Checks that the settings describe an image that can be rendered.
+    pub fn validate(&self) -> Result<(), String> {
+        let samples = plotting_size(self.size);
+        if samples.0 == 0 || samples.1 == 0 {
+            return Err(format!(
+                "image size {}x{} leaves no room inside the axis frame, both sides need more than {} pixels",
+                self.size.0, self.size.1, FRAME_SIZE
+            ));
+        }
+        if self.iterations == 0 {
+            return Err("at least one iteration is needed".to_string());
+        }
+        if !(self.escape_radius >= DEFAULT_ESCAPE_RADIUS && self.escape_radius.is_finite()) {
+            return Err(format!(
+                "escape radius {} is not a finite number of at least {}",
+                self.escape_radius, DEFAULT_ESCAPE_RADIUS
+            ));
+        }
+        if !(1..=MAX_SUPERSAMPLING).contains(&self.supersampling) {
+            return Err(format!(
+                "supersampling {} is not between 1 and {}",
+                self.supersampling, MAX_SUPERSAMPLING
+            ));
+        }
+        if let Some(threshold) = self.adaptive_threshold {
+            if !(threshold >= 0. && threshold.is_finite()) {
+                return Err(format!(
+                    "adaptive threshold {} is not a finite number of at least 0",
+                    threshold
+                ));
+            }
+            if self.supersampling == 1 {
+                return Err("adaptive threshold needs a supersampling above 1".to_string());
+            }
+        }
+        if self.perturbation && self.fractal != FractalKind::Mandelbrot {
+            return Err("perturbation only works for the Mandelbrot set".to_string());
+        }
+        if self.perturbation && self.subdivide {
+            return Err("subdivision doesn't work with perturbation".to_string());
+        }
+        if self.simd {
+            if self.fractal != FractalKind::Mandelbrot || self.perturbation {
+                return Err(
+                    "SIMD only works for the Mandelbrot set without perturbation".to_string(),
+                );
+            }
+            if self.precision.resolve(&self.view, samples) != Precision::F64 {
+                return Err("SIMD only works with f64 precision".to_string());
+            }
+            if !simd::supports(self.track()) {
+                return Err("SIMD doesn't work with colorings that track the orbit".to_string());
+            }
+            if self.subdivide {
+                return Err("SIMD doesn't work with subdivision".to_string());
+            }
+        }
+        if !matches!(self.precision, Precision::Auto | Precision::F64)
+            && self.fractal != FractalKind::Mandelbrot
+        {
+            return Err(
+                "only the Mandelbrot set can be iterated with a precision other than f64"
+                    .to_string(),
+            );
+        }
+        self.fractal.validate()?;
+        self.coloring.validate()?;
+        if let Some(trap) = &self.trap {
+            trap.validate()?;
+        }
+        if let Some(lighting) = &self.lighting {
+            if !self.fractal.has_derivative() {
+                return Err("lighting needs a fractal with a derivative".to_string());
+            }
+            lighting.validate()?;
+        }
+        self.palette.validate()?;
+        self.view.validate()
+    }
+
+    /// Escape radius orbits are actually iterated with: `escape_radius`, or
+    /// the smallest radius the coloring works well with if that is larger.
+    pub fn bailout(&self) -> f64 {
+        self.escape_radius.max(self.coloring.min_escape_radius())
+    }
+
+    /// Quantities the colorings need tracked along the orbits.
+    pub fn track(&self) -> Track {
+        let (exterior, interior) = (self.coloring.track(), self.interior.track());
+        Track {
+            derivative: exterior.derivative || interior.derivative || self.lighting.is_some(),
+            min_distance: exterior.min_distance || interior.min_distance,
+            cycle: exterior.cycle || interior.cycle,
+            final_z: exterior.final_z || interior.final_z,
+            trap: self.trap.as_ref().map(OrbitTrap::shape),
+            average: exterior.average,
+        }
+    }
+}
+
+/// Space between the canvas border and the chart.
+const MARGIN: u32 = 20;
+/// Width of the axis label areas below and left of the plotting area.
+const LABEL_AREA: u32 = 10;
+
+/// Pixels taken up by the chart frame along either side of the canvas.
+pub const FRAME_SIZE: u32 = 2 * MARGIN + LABEL_AREA;
+
+/// Size of the plotting area inside the chart frame of a `size` canvas, i.e.
+/// the number of samples taken horizontally and vertically.
+pub fn plotting_size(size: (u32, u32)) -> (u32, u32) {
+    (
+        size.0.saturating_sub(FRAME_SIZE),
+        size.1.saturating_sub(FRAME_SIZE),
+    )
+}
+
+/// Escape results of the center of every pixel of a grid of
+/// `samples` pixels covering `settings.view`.
+pub fn iterate<F: Fractal + Sync>(
+    settings: &Settings,
+    fractal: &F,
+    samples: (u32, u32),
+) -> Buffer<EscapeResult> {
+    let bailout = settings.bailout();
+    let track = settings.track();
+    let mut escapes = Buffer::new(samples.0, samples.1, EscapeResult::default());
+    escapes.fill_rows(settings.threads, |y, row| {
+        for (x, result) in (0..).zip(row) {
+            let c = settings.view.point(x, y, samples);
+            *result = escape_with(fractal, &c, settings.iterations, bailout, track);
+        }
+    });
+    escapes
+}
+
+/// Colors of a render.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub colors: Buffer<RGBColor>,
+    /// Number of pixels that were supersampled: all of them with uniform
+    /// supersampling, the high-variance ones with adaptive supersampling,
+    /// see [`Settings::adaptive_threshold`].
+    pub refined: u64,
+}
+
+/// Colors of the pixels whose escape results are `escapes`.
+///
+/// With supersampling the samples laid out by `settings.sample_pattern` are
+/// iterated here, except for the center of the pixel which is taken from
+/// `escapes`, and their colors are averaged in linear light. Colorings that
+/// depend on the whole render, like the histogram coloring, only look at the
+/// samples in `escapes`.
+pub fn shade<F: Fractal + Sync>(
+    settings: &Settings,
+    fractal: &F,
+    escapes: &Buffer<EscapeResult>,
+) -> Image {
+    let bailout = settings.bailout();
+    let track = settings.track();
+    let samples = escapes.size();
+    shade_with(settings, fractal.degree(), escapes, |x, y| {
+        let c = settings.view.sample(x, y, samples);
+        escape_with(fractal, &c, settings.iterations, bailout, track)
+    })
+}
+
+/// Same as [`shade`] for a fractal of the given `degree`, the extra samples
+/// at fractional pixel coordinates `(x, y)` being computed by `sample`.
+pub(crate) fn shade_with<S>(
+    settings: &Settings,
+    degree: f64,
+    escapes: &Buffer<EscapeResult>,
+    sample: S,
+) -> Image
+where
+    S: Fn(f64, f64) -> EscapeResult + Sync,
+{
+    let samples = escapes.size();
+    let n = settings.supersampling;
+    let step = settings.view.step(samples);
+    let mut context = ColorContext {
+        num_iterations: settings.iterations,
+        escape_radius: settings.bailout(),
+        degree,
+        pixel_size: step.0.max(step.1),
+        histogram: None,
+    };
+    context.histogram = settings.coloring.histogram(escapes.as_slice(), &context);
+    let color = |result: &EscapeResult| {
+        let palette = &settings.palette;
+        let color = if result.escaped() {
+            settings.coloring.color(palette, result, &context)
+        } else {
+            settings.interior.color(palette, result, &context)
+        };
+        let color = match (&settings.trap, result.trap) {
+            (Some(trap), Some(hit)) => trap.color(palette, hit, color),
+            _ => color,
+        };
+        match (&settings.lighting, result.derivative) {
+            (Some(lighting), Some(dz)) if result.escaped() => lighting.shade(color, result.z, dz),
+            _ => color,
+        }
+    };
+    let pattern = settings.sample_pattern;
+    let supersample = |x: u32, y: u32| {
+        average((0..n * n).map(|i| {
+            let result = match pattern.offset(n, i, (x, y)) {
+                (0.5, 0.5) => escapes[(x, y)],
+                (dx, dy) => sample(x as f64 + dx, y as f64 + dy),
+            };
+            color(&result)
+        }))
+    };
+
+    let mut colors = Buffer::new(samples.0, samples.1, BLACK);
+    if n == 1 {
+        colors.fill_rows(settings.threads, |y, row| {
+            for (x, pixel) in (0..).zip(row) {
+                *pixel = color(&escapes[(x, y)]);
+            }
+        });
+        return Image { colors, refined: 0 };
+    }
+    let Some(threshold) = settings.adaptive_threshold else {
+        colors.fill_rows(settings.threads, |y, row| {
+            for (x, pixel) in (0..).zip(row) {
+                *pixel = supersample(x, y);
+            }
+        });
+        let refined = samples.0 as u64 * samples.1 as u64;
+        return Image { colors, refined };
+    };
+
+    //  Adaptive supersampling refines the pixels of a first render
+    let first = escapes.map(color);
+    let position = |result: &EscapeResult| {
+        let t = settings.coloring.position(result, &context)?;
+        Some(t * settings.palette.repeat)
+    };
+    let refine = high_variance(settings, escapes, &first, position, threshold);
+    colors.fill_rows(settings.threads, |y, row| {
+        for (x, pixel) in (0..).zip(row) {
+            *pixel = match refine[(x, y)] {
+                true => supersample(x, y),
+                false => first[(x, y)],
+            };
+        }
+    });
+    let refined = refine.as_slice().iter().filter(|&&refine| refine).count() as u64;
+    Image { colors, refined }
+}
+
+/// Pixels that differ from one of their eight neighbours by more than
+/// `threshold` in their `colors`, per linear-light channel, or in the
+/// `position` along the palette of the escape value, in runs through the
+/// gradient. Pixels next to one that escaped when they didn't, or the other
+/// way around, always differ.
+fn high_variance<P>(
+    settings: &Settings,
+    escapes: &Buffer<EscapeResult>,
+    colors: &Buffer<RGBColor>,
+    position: P,
+    threshold: f64,
+) -> Buffer<bool>
+where
+    P: Fn(&EscapeResult) -> Option<f64> + Sync,
+{
+    let (width, height) = escapes.size();
+    let linear = colors.map(|&color| to_linear(color));
+    let positions = escapes.map(position);
+    let differ = |a: (u32, u32), b: (u32, u32)| {
+        if escapes[a].escaped() != escapes[b].escaped() {
+            return true;
+        }
+        let positions = match (positions[a], positions[b]) {
+            (Some(a), Some(b)) => (a - b).abs() > threshold,
+            _ => false,
+        };
+        positions
+            || (0..3).any(|channel| (linear[a][channel] - linear[b][channel]).abs() > threshold)
+    };
+
+    let mut refine = Buffer::new(width, height, false);
+    refine.fill_rows(settings.threads, |y, row| {
+        for (x, pixel) in (0u32..).zip(row) {
+            let xs = x.saturating_sub(1)..=(x + 1).min(width - 1);
+            let ys = y.saturating_sub(1)..=(y + 1).min(height - 1);
+            *pixel = ys
+                .flat_map(|v| xs.clone().map(move |u| (u, v)))
+                .any(|neighbour| differ((x, y), neighbour));
+        }
+    });
+    refine
+}
+
+/// Colors of a grid of `samples` pixels showing `fractal`.
+pub fn render<F: Fractal + Sync>(settings: &Settings, fractal: &F, samples: (u32, u32)) -> Image {
+    let escapes = match settings.subdivide {
+        true => iterate_subdivided(settings, fractal, samples),
+        false => iterate(settings, fractal, samples),
+    };
+    shade(settings, fractal, &escapes)
+}
+
+/// Same as [`render`] for a fractal of the given `degree`, the samples at
+/// fractional pixel coordinates `(x, y)` being computed by `sample`.
+pub(crate) fn render_with<S>(
+    settings: &Settings,
+    samples: (u32, u32),
+    degree: f64,
+    sample: S,
+) -> Image
+where
+    S: Fn(f64, f64) -> EscapeResult + Sync,
+{
+    let escapes = match settings.subdivide {
+        true => {
+            subdivide_with(settings, samples, |x, y| {
+                sample(x as f64 + 0.5, y as f64 + 0.5)
+            })
+            .0
+        }
+        false => {
+            let mut escapes = Buffer::new(samples.0, samples.1, EscapeResult::default());
+            escapes.fill_rows(settings.threads, |y, row| {
+                for (x, result) in (0..).zip(row) {
+                    *result = sample(x as f64 + 0.5, y as f64 + 0.5);
+                }
+            });
+            escapes
+        }
+    };
+    shade_with(settings, degree, &escapes, sample)
+}
+
+/// Same as [`render`] for the Mandelbrot set iterated with the precision
+/// `T`, see [`mandelbrot_with`].
+pub fn render_mandelbrot<T: Real>(settings: &Settings, samples: (u32, u32)) -> Image {
+    let bailout = settings.bailout();
+    let track = settings.track();
+    let center = settings.view.center_as::<T>();
+    render_with(settings, samples, Mandelbrot.degree(), |x, y| {
+        let offset = settings.view.offset(x, y, samples);
+        let c = center + Complex::new(T::from_f64(offset.re), T::from_f64(offset.im));
+        mandelbrot_with(&c, settings.iterations, bailout, track)
+    })
+}
+
+/// Same as [`render`] for the fractal selected by `settings.fractal`, with
+/// the precision selected by `settings.precision`.
+pub fn render_pixels(settings: &Settings, samples: (u32, u32)) -> Image {
+    match settings.fractal {
+        FractalKind::Mandelbrot if settings.perturbation => render_perturbed(settings, samples),
+        FractalKind::Mandelbrot => match settings.precision.resolve(&settings.view, samples) {
+            Precision::F32 => render_mandelbrot::<f32>(settings, samples),
+            Precision::DoubleDouble => render_mandelbrot::<DoubleDouble>(settings, samples),
+            Precision::Auto | Precision::F64 if settings.simd => render_lanes(settings, samples),
+            Precision::Auto | Precision::F64 => render(settings, &Mandelbrot, samples),
+        },
+        FractalKind::Julia { c } => render(settings, &Julia { c }, samples),
+        FractalKind::BurningShip => render(settings, &BurningShip, samples),
+        FractalKind::Tricorn => render(settings, &Tricorn, samples),
+        FractalKind::Multibrot { power } => render(settings, &Multibrot { power }, samples),
+    }
+}
+
+/// Copies `image` onto `area` in one go, pixel `(0, 0)` going to the
+/// top-left corner.
+pub fn blit<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    image: &Buffer<RGBColor>,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let bitmap = BitMapElement::with_owned_buffer((0, 0), image.size(), image.to_rgb_bytes())
+        .expect("three bytes per pixel");
+    area.draw(&bitmap)
+}
+
+/// Fills `root` and draws the chart frame around the view, returning the
+/// plotting area inside the frame in pixel coordinates.
+fn draw_frame<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    settings: &Settings,
+) -> Result<DrawingArea<DB, Shift>, DrawingAreaErrorKind<DB::ErrorType>> {
+    root.fill(&WHITE)?;
+
+    let view = &settings.view;
+    let mut chart = ChartBuilder::on(root)
+        .margin(MARGIN)
+        .x_label_area_size(LABEL_AREA)
+        .y_label_area_size(LABEL_AREA)
+        .build_cartesian_2d(view.re(), view.im())?;
+
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .disable_y_mesh()
+        .draw()?;
+
+    Ok(chart.plotting_area().strip_coord_spec())
+}
+
+/// Draws the set described by `settings` onto `root`, returning the image
+/// inside the frame.
+///
+/// The view is framed by a chart with small axis areas; the plotting area
+/// inside the frame is filled with the rendered pixels.
+pub fn draw_mandelbrot<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    settings: &Settings,
+) -> Result<Image, DrawingAreaErrorKind<DB::ErrorType>> {
+    let area = draw_frame(root, settings)?;
+    let image = render_pixels(settings, area.dim_in_pixel());
+    blit(&area, &image.colors)?;
+    root.present()?;
+    Ok(image)
+}
+
+/// Same as [`draw_mandelbrot`], but draws `fractal` instead of the one
+/// selected by `settings.fractal`.
+pub fn draw_fractal<DB: DrawingBackend, F: Fractal + Sync>(
+    root: &DrawingArea<DB, Shift>,
+    settings: &Settings,
+    fractal: &F,
+) -> Result<Image, DrawingAreaErrorKind<DB::ErrorType>> {
+    let area = draw_frame(root, settings)?;
+    let image = render(settings, fractal, area.dim_in_pixel());
+    blit(&area, &image.colors)?;
+    root.present()?;
+    Ok(image)
+}
+
+/// Renders `settings` into the bitmap image at `path`; the format follows the
+/// file extension.
+pub fn render_to_file<P: AsRef<Path>>(
+    path: P,
+    settings: &Settings,
+) -> Result<Image, Box<dyn Error>> {
+    let root = BitMapBackend::new(path.as_ref(), settings.size).into_drawing_area();
+    Ok(draw_mandelbrot(&root, settings)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bignum::Decimal;
+    use crate::escape::{ComplexDouble, SMOOTH_ESCAPE_RADIUS};
+
+    #[test]
+    fn plotting_size_test() {
+        assert_eq!(plotting_size((1600, 1200)), (1550, 1150));
+        assert_eq!(plotting_size((40, 1200)), (0, 1150));
+    }
+
+    #[test]
+    fn threads_test() {
+        let settings = Settings {
+            view: View::new(-0.8..-0.7, 0.05..0.15),
+            iterations: 500,
+            supersampling: 2,
+            ..Settings::default()
+        };
+        let colors = |threads| {
+            let settings = Settings {
+                threads,
+                ..settings.clone()
+            };
+            render(&settings, &Mandelbrot, (67, 41))
+        };
+
+        let single = colors(1);
+        assert_eq!(single.colors.size(), (67, 41));
+        for threads in [2, 7, 64] {
+            assert!(colors(threads) == single, "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn bailout_test() {
+        let settings = Settings {
+            escape_radius: 10.,
+            ..Settings::default()
+        };
+        assert_eq!(settings.bailout(), 10.);
+
+        let smooth = Settings {
+            coloring: Coloring::Smooth,
+            ..settings.clone()
+        };
+        assert_eq!(smooth.bailout(), SMOOTH_ESCAPE_RADIUS);
+        assert!(render(&smooth, &Mandelbrot, (30, 20)) != render(&settings, &Mandelbrot, (30, 20)));
+    }
+
+    #[test]
+    fn adaptive_test() {
+        let settings = Settings {
+            view: View::new(-0.8..-0.7, 0.05..0.15),
+            iterations: 200,
+            coloring: Coloring::Smooth,
+            supersampling: 3,
+            adaptive_threshold: Some(0.05),
+            ..Settings::default()
+        };
+        let samples = (60, 40);
+        let adaptive = render(&settings, &Mandelbrot, samples);
+        let single = Settings {
+            supersampling: 1,
+            ..settings.clone()
+        };
+        let single = render(&single, &Mandelbrot, samples);
+        let uniform = Settings {
+            adaptive_threshold: None,
+            ..settings.clone()
+        };
+        let uniform = render(&uniform, &Mandelbrot, samples);
+        assert_eq!((single.refined, uniform.refined), (0, 60 * 40));
+        assert!(adaptive.refined > 0 && adaptive.refined < 60 * 40 / 2);
+
+        //  Every pixel is either left alone or supersampled like uniformly
+        for (x, y, &color) in adaptive.colors.pixels() {
+            assert!(color == single.colors[(x, y)] || color == uniform.colors[(x, y)]);
+        }
+
+        //  The boundary is refined whatever the threshold
+        let boundary = Settings {
+            adaptive_threshold: Some(10.),
+            ..settings.clone()
+        };
+        let boundary = render(&boundary, &Mandelbrot, samples);
+        assert!(boundary.refined > 0 && boundary.refined < adaptive.refined);
+    }
+
+    #[test]
+    fn blit_test() {
+        let image = Buffer::from_vec(2, 1, vec![RGBColor(1, 2, 3), RGBColor(4, 5, 6)]);
+        let mut bytes = vec![0; 4 * 2 * 3];
+        {
+            let area = BitMapBackend::with_buffer(&mut bytes, (4, 2))
+                .into_drawing_area()
+                .shrink((1, 1), (3, 1));
+            blit(&area, &image).unwrap();
+            area.present().unwrap();
+        }
+        let mut expected = vec![0; 4 * 2 * 3];
+        expected[15..21].copy_from_slice(&[1, 2, 3, 4, 5, 6]);
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn validate_test() {
+        assert_eq!(Settings::default().validate(), Ok(()));
+
+        let invalid = [
+            Settings {
+                size: (50, 1200),
+                ..Settings::default()
+            },
+            Settings {
+                adaptive_threshold: Some(-0.1),
+                supersampling: 2,
+                ..Settings::default()
+            },
+            Settings {
+                adaptive_threshold: Some(0.1),
+                ..Settings::default()
+            },
+            Settings {
+                iterations: 0,
+                ..Settings::default()
+            },
+            Settings {
+                escape_radius: 1.5,
+                ..Settings::default()
+            },
+            Settings {
+                escape_radius: f64::INFINITY,
+                coloring: Coloring::Smooth,
+                ..Settings::default()
+            },
+            Settings {
+                palette: Palette {
+                    repeat: -1.,
+                    ..Palette::default()
+                },
+                ..Settings::default()
+            },
+            Settings {
+                coloring: Coloring::Distance { thickness: -1. },
+                ..Settings::default()
+            },
+            Settings {
+                lighting: Some(Lighting {
+                    shininess: 0.,
+                    ..Lighting::default()
+                }),
+                ..Settings::default()
+            },
+            Settings {
+                fractal: FractalKind::BurningShip,
+                lighting: Some(Lighting::default()),
+                ..Settings::default()
+            },
+            Settings {
+                fractal: FractalKind::Tricorn,
+                perturbation: true,
+                ..Settings::default()
+            },
+            Settings {
+                perturbation: true,
+                subdivide: true,
+                ..Settings::default()
+            },
+            Settings {
+                fractal: FractalKind::Tricorn,
+                simd: true,
+                ..Settings::default()
+            },
+            Settings {
+                precision: Precision::F32,
+                simd: true,
+                ..Settings::default()
+            },
+            Settings {
+                view: View::centered(ComplexDouble::new(-0.75, 0.1), 1e14, 1.),
+                simd: true,
+                ..Settings::default()
+            },
+            Settings {
+                coloring: Coloring::Distance { thickness: 1. },
+                simd: true,
+                ..Settings::default()
+            },
+            Settings {
+                subdivide: true,
+                simd: true,
+                ..Settings::default()
+            },
+            Settings {
+                fractal: FractalKind::BurningShip,
+                precision: Precision::DoubleDouble,
+                ..Settings::default()
+            },
+            Settings {
+                supersampling: 0,
+                ..Settings::default()
+            },
+            Settings {
+                view: View {
+                    radius: Decimal::from_f64(-1.),
+                    ..View::default()
+                },
+                ..Settings::default()
+            },
+            Settings {
+                fractal: FractalKind::Multibrot { power: 0 },
+                ..Settings::default()
+            },
+        ];
+        for settings in invalid {
+            assert!(settings.validate().is_err(), "{:?}", settings);
+        }
+    }
+}
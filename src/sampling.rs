@@ -0,0 +1,166 @@
+//! Positions of the samples taken inside a pixel.
+//!
+//! Without supersampling every pixel is sampled at its center. With `n`
+//! samples per pixel along each axis, a [`SamplePattern`] lays out the `n²`
+//! samples, whose colors are averaged in linear light.
+
+use std::fmt;
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Layout of the samples of a supersampled pixel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum SamplePattern {
+    /// Centers of the cells of a regular `n × n` grid.
+    #[default]
+    Grid,
+    /// Grid sheared so that no two samples share a row or a column of the
+    /// finer `n² × n²` grid, which smooths nearly horizontal and vertical
+    /// edges with `n²` shades instead of `n`.
+    RotatedGrid,
+    /// Random point inside every cell of the grid, which trades the moiré
+    /// of regular patterns for noise. The points only depend on the pixel
+    /// and on `seed`, so renders can be repeated.
+    Jitter {
+        #[serde(default, with = "seed")]
+        seed: u64,
+    },
+}
+
+impl SamplePattern {
+    /// Position of sample `i` of the `n²` samples of `pixel`, relative to the
+    /// top-left corner of the pixel, both coordinates in `0..1`.
+    pub fn offset(&self, n: u32, i: u32, pixel: (u32, u32)) -> (f64, f64) {
+        let (column, row) = ((i % n) as f64, (i / n) as f64);
+        let n = n as f64;
+        match *self {
+            SamplePattern::Grid => ((column + 0.5) / n, (row + 0.5) / n),
+            SamplePattern::RotatedGrid => (
+                (column + (row + 0.5) / n) / n,
+                (row + (n - 1. - column + 0.5) / n) / n,
+            ),
+            SamplePattern::Jitter { seed } => {
+                let hash = [pixel.0, pixel.1, i]
+                    .into_iter()
+                    .fold(seed, |hash, value| mix(hash ^ value as u64));
+                let (u, v) = (unit(hash), unit(mix(hash)));
+                ((column + u) / n, (row + v) / n)
+            }
+        }
+    }
+}
+
+/// Serialization of jitter seeds. TOML integers stop at `i64::MAX`, so
+/// larger seeds are written as strings.
+mod seed {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(seed: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        match i64::try_from(*seed) {
+            Ok(seed) => serializer.serialize_i64(seed),
+            Err(_) => serializer.collect_str(seed),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        struct SeedVisitor;
+
+        impl Visitor<'_> for SeedVisitor {
+            type Value = u64;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a non-negative integer or a string holding one")
+            }
+
+            fn visit_u64<E: de::Error>(self, seed: u64) -> Result<u64, E> {
+                Ok(seed)
+            }
+
+            fn visit_i64<E: de::Error>(self, seed: i64) -> Result<u64, E> {
+                u64::try_from(seed).map_err(|_| E::custom(format!("seed {} is negative", seed)))
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<u64, E> {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(SeedVisitor)
+    }
+}
+
+/// Next state of the SplitMix64 generator after `state`, which also hashes
+/// well.
+fn mix(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Number in `0..1` made of the high bits of `hash`.
+fn unit(hash: u64) -> f64 {
+    (hash >> 11) as f64 * 0.5f64.powi(53)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offsets(pattern: SamplePattern, n: u32, pixel: (u32, u32)) -> Vec<(f64, f64)> {
+        (0..n * n).map(|i| pattern.offset(n, i, pixel)).collect()
+    }
+
+    #[test]
+    fn grid_test() {
+        assert_eq!(offsets(SamplePattern::Grid, 1, (3, 4)), [(0.5, 0.5)]);
+        assert_eq!(
+            offsets(SamplePattern::Grid, 2, (3, 4)),
+            [(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]
+        );
+        assert_eq!(offsets(SamplePattern::RotatedGrid, 1, (3, 4)), [(0.5, 0.5)]);
+        assert_eq!(
+            offsets(SamplePattern::RotatedGrid, 2, (3, 4)),
+            [
+                (0.125, 0.375),
+                (0.625, 0.125),
+                (0.375, 0.875),
+                (0.875, 0.625)
+            ]
+        );
+    }
+
+    #[test]
+    fn rotated_grid_test() {
+        //  Every row and column of the fine grid holds one sample
+        for n in 2..=5 {
+            let fine = |x: f64| (x * (n * n) as f64).floor() as u32;
+            let offsets = offsets(SamplePattern::RotatedGrid, n, (0, 0));
+            let mut columns: Vec<_> = offsets.iter().map(|&(x, _)| fine(x)).collect();
+            let mut rows: Vec<_> = offsets.iter().map(|&(_, y)| fine(y)).collect();
+            columns.sort();
+            rows.sort();
+            assert_eq!(columns, (0..n * n).collect::<Vec<_>>());
+            assert_eq!(rows, (0..n * n).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn jitter_test() {
+        let jitter = SamplePattern::Jitter { seed: 7 };
+        let samples = offsets(jitter, 3, (10, 20));
+        assert_eq!(samples, offsets(jitter, 3, (10, 20)));
+        assert_ne!(samples, offsets(jitter, 3, (11, 20)));
+        assert_ne!(
+            samples,
+            offsets(SamplePattern::Jitter { seed: 8 }, 3, (10, 20))
+        );
+
+        //  One sample per cell of the grid
+        for (i, (x, y)) in (0..).zip(samples) {
+            assert_eq!(((x * 3.) as u32, (y * 3.) as u32), (i % 3, i / 3));
+        }
+    }
+}
@@ -0,0 +1,319 @@
+//! Scene files describing a complete render.
+//!
+//! A scene bundles the render [`Settings`] with the image file they are
+//! written to. Scenes are stored as TOML or JSON, picked by file extension;
+//! every key except `output` may be left out and falls back to its default:
+//!
+//! ```toml
+//! output = "seahorses.png"
+//!
+//! [render]
+//! size = [1600, 1200]
+//! iterations = 500
+//! supersampling = 2
+//! sample_pattern = { type = "rotated-grid" }
+//!
+//! [render.view]
+//! center = ["-0.745", "0.1"]
+//! radius = "0.05"
+//! aspect = 1.348
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::render::{render_to_file, Image, Settings};
+use crate::trap::OrbitTrap;
+
+/// Image formats the bitmap backend can encode, by file extension.
+pub const OUTPUT_FORMATS: [&str; 4] = ["png", "bmp", "jpg", "jpeg"];
+
+/// Render settings together with the file the image is written to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    /// Image file to write; the format follows the extension.
+    pub output: PathBuf,
+    /// What to draw.
+    #[serde(default)]
+    pub render: Settings,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Scene {
+            output: PathBuf::from("mandelbrot.png"),
+            render: Settings::default(),
+        }
+    }
+}
+
+/// Serialization formats of scene files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// Format of the scene file at `path`, judged by its extension.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "toml" => Some(Format::Toml),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Reasons a scene can't be loaded or rendered.
+#[derive(Debug)]
+pub enum SceneError {
+    /// The scene file couldn't be read.
+    Io(io::Error),
+    /// The scene file has neither a `.toml` nor a `.json` extension.
+    UnknownFormat(PathBuf),
+    /// The scene file isn't a well-formed scene.
+    Parse(String),
+    /// The scene is well-formed but can't be rendered.
+    Invalid(String),
+    /// The scene can't be written in the requested format.
+    Write(String),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(err) => write!(f, "can't read scene: {}", err),
+            SceneError::UnknownFormat(path) => write!(
+                f,
+                "can't tell the format of scene {}, expected a .toml or .json file",
+                path.display()
+            ),
+            SceneError::Parse(message) => write!(f, "malformed scene: {}", message),
+            SceneError::Invalid(message) => write!(f, "invalid scene: {}", message),
+            SceneError::Write(message) => write!(f, "can't write scene: {}", message),
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(err: io::Error) -> Self {
+        SceneError::Io(err)
+    }
+}
+
+impl Scene {
+    /// Reads and validates the scene file at `path`. Relative paths of trap
+    /// images are taken relative to the directory of the file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+        let path = path.as_ref();
+        let format =
+            Format::from_path(path).ok_or_else(|| SceneError::UnknownFormat(path.to_owned()))?;
+        let mut scene = Scene::read(&fs::read_to_string(path)?, format)?;
+        scene.resolve_paths(path.parent().unwrap_or(Path::new("")))?;
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    /// Parses and validates a scene written in `format`. Relative paths of
+    /// trap images are taken relative to the working directory.
+    pub fn parse(source: &str, format: Format) -> Result<Scene, SceneError> {
+        let mut scene = Scene::read(source, format)?;
+        scene.resolve_paths(Path::new(""))?;
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    /// Scene written in `format`, with the files it names not loaded yet.
+    fn read(source: &str, format: Format) -> Result<Scene, SceneError> {
+        match format {
+            Format::Toml => {
+                toml::from_str(source).map_err(|err| SceneError::Parse(err.to_string()))
+            }
+            Format::Json => {
+                serde_json::from_str(source).map_err(|err| SceneError::Parse(err.to_string()))
+            }
+        }
+    }
+
+    /// Loads the trap image of the scene, its path taken relative to `dir`
+    /// if it is relative.
+    pub fn resolve_paths(&mut self, dir: &Path) -> Result<(), SceneError> {
+        if let Some(OrbitTrap::Image { image, .. }) = &mut self.render.trap {
+            image.resolve(dir).map_err(|err| {
+                SceneError::Invalid(format!("trap image {}: {}", image.path().display(), err))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Writes out every setting of the scene in `format`.
+    pub fn to_string(&self, format: Format) -> Result<String, SceneError> {
+        match format {
+            Format::Toml => toml::to_string(self).map_err(|err| SceneError::Write(err.to_string())),
+            Format::Json => {
+                serde_json::to_string_pretty(self).map_err(|err| SceneError::Write(err.to_string()))
+            }
+        }
+    }
+
+    /// Checks that the scene can be rendered.
+    pub fn validate(&self) -> Result<(), SceneError> {
+        let extension = self
+            .output
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        if !matches!(extension, Some(extension) if OUTPUT_FORMATS.contains(&extension.as_str())) {
+            return Err(SceneError::Invalid(format!(
+                "output {} doesn't end in one of {}",
+                self.output.display(),
+                OUTPUT_FORMATS.join(", ")
+            )));
+        }
+
+        self.render.validate().map_err(SceneError::Invalid)
+    }
+
+    /// Renders the scene into its output file.
+    pub fn render(&self) -> Result<Image, Box<dyn Error>> {
+        render_to_file(&self.output, &self.render)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bignum::{ComplexDecimal, Decimal};
+    use crate::palette::{ColorSpace, Gradient, Palette, Stop};
+    use crate::sampling::SamplePattern;
+    use plotters::style::RGBColor;
+
+    fn scene() -> Scene {
+        let mut scene = Scene {
+            output: PathBuf::from("seahorses.png"),
+            ..Scene::default()
+        };
+        scene.render.view.center = ComplexDecimal::new(
+            "-0.74364388703715870475219150611477419572220"
+                .parse()
+                .unwrap(),
+            "0.13182590420531197049313205638513923380545"
+                .parse()
+                .unwrap(),
+        );
+        scene.render.view.radius = "2.5e-35".parse().unwrap();
+        scene.render.iterations = 500;
+        scene.render.supersampling = 2;
+        scene.render.sample_pattern = SamplePattern::Jitter { seed: 42 };
+        scene.render.palette = Palette {
+            gradient: Gradient::Stops {
+                stops: vec![
+                    Stop::new(0., RGBColor(0, 7, 100)),
+                    Stop::new(0.25, RGBColor(255, 255, 255)),
+                    Stop::new(1., RGBColor(255, 170, 0)),
+                ],
+            },
+            space: ColorSpace::Oklab,
+            repeat: 3.,
+            offset: 0.5,
+        };
+        scene
+    }
+
+    #[test]
+    fn round_trip_test() {
+        //  Seeds beyond TOML integers are written as strings
+        let mut jittered = scene();
+        jittered.render.sample_pattern = SamplePattern::Jitter { seed: u64::MAX };
+        for scene in [scene(), jittered] {
+            for format in [Format::Toml, Format::Json] {
+                let source = scene.to_string(format).unwrap();
+                assert_eq!(Scene::parse(&source, format).unwrap(), scene, "{}", source);
+            }
+        }
+    }
+
+    #[test]
+    fn defaults_test() {
+        let scene = Scene::parse("output = 'mandelbrot.png'", Format::Toml).unwrap();
+        assert_eq!(scene, Scene::default());
+
+        let scene = Scene::parse(
+            r#"{"output": "a.bmp", "render": {"iterations": 50}}"#,
+            Format::Json,
+        )
+        .unwrap();
+        assert_eq!(scene.render.iterations, 50);
+        assert_eq!(scene.render.view, Settings::default().view);
+
+        //  Coordinates may also be plain numbers
+        let scene = Scene::parse(
+            "output = 'a.png'\n[render.view]\ncenter = [-0.745, 1]\nradius = 0.05",
+            Format::Toml,
+        )
+        .unwrap();
+        assert_eq!(
+            scene.render.view.center,
+            ComplexDecimal::new(Decimal::from_f64(-0.745), Decimal::from_f64(1.))
+        );
+        assert_eq!(scene.render.view.radius.to_string(), "0.05");
+    }
+
+    #[test]
+    fn invalid_test() {
+        let parse = |source| Scene::parse(source, Format::Toml);
+
+        assert!(matches!(
+            parse("output = 'a.png'\n[render]\niteration = 5"),
+            Err(SceneError::Parse(_))
+        ));
+        assert!(matches!(
+            parse("output = 'a.txt'"),
+            Err(SceneError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse("output = 'a.png'\n[render]\nsupersampling = 100"),
+            Err(SceneError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse("output = 'a.png'\n[render.fractal]\ntype = 'cauliflower'"),
+            Err(SceneError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn trap_image_test() {
+        let dir = std::env::temp_dir().join("mandelbrot_scene_trap_test");
+        fs::create_dir_all(&dir).unwrap();
+        image::RgbaImage::new(2, 2)
+            .save(dir.join("trap.png"))
+            .unwrap();
+        let source = "output = 'a.png'\n[render.trap]\ntype = 'image'\nimage = 'trap.png'";
+        let path = dir.join("scene.toml");
+        fs::write(&path, source).unwrap();
+
+        //  Found next to the scene file, wherever it is loaded from
+        let loaded = Scene::load(&path);
+        let mut parsed = Scene::read(source, Format::Toml).unwrap();
+        //  Until the image is loaded, the scene can't be rendered
+        assert!(parsed.validate().is_err());
+        let resolved = parsed.resolve_paths(&dir);
+        fs::remove_dir_all(dir).unwrap();
+        let loaded = loaded.unwrap();
+        match &loaded.render.trap {
+            Some(OrbitTrap::Image { image, .. }) => assert_eq!(image.path(), Path::new("trap.png")),
+            trap => panic!("{:?}", trap),
+        }
+        resolved.unwrap();
+        assert_eq!(parsed, loaded);
+    }
+}
@@ -0,0 +1,544 @@
+//! Vectorized escape-time kernel for the Mandelbrot set.
+//!
+//! [`mandelbrot_lanes`] iterates [`LANES`] points at once, with whatever
+//! the CPU supports, as detected at runtime: AVX-512 with all points in one
+//! register, AVX with two registers of 4 points, or plain arrays the compiler
+//! may vectorize on its own. Points that escape or come back to an earlier
+//! point are masked out, keeping their count and last point, while the
+//! others go on until all of them are done or the iteration limit is reached.
+//!
+//! Every lane does the same operations in the same order as
+//! [`mandelbrot_with`] for `f64`, including its early-outs: points in the
+//! main cardioid or the period 2 bulb aren't iterated, and Brent's cycle
+//! detection stops the others with the same tolerance. The escape results
+//! agree with it exactly.
+
+use crate::buffer::Buffer;
+use crate::escape::{
+    mandelbrot_with, periodicity_tolerance, ComplexDouble, EscapeResult, Status, Track,
+};
+use crate::fractal::{Fractal, Mandelbrot};
+use crate::render::{render, shade_with, Image, Settings};
+
+/// Number of points iterated at once.
+pub const LANES: usize = 8;
+
+/// Mask with a bit set for every lane.
+const ALL_LANES: u8 = u8::MAX;
+
+/// Real and imaginary parts of the points of a lane group.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Points {
+    re: [f64; LANES],
+    im: [f64; LANES],
+}
+
+/// Outcome of iterating a lane group.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Orbits {
+    /// Lanes whose orbit escaped.
+    escaped: u8,
+    /// Lanes whose orbit came back to an earlier point.
+    periodic: u8,
+    count: [u32; LANES],
+    z: Points,
+}
+
+impl Orbits {
+    fn new(num_iterations: u32) -> Self {
+        Orbits {
+            escaped: 0,
+            periodic: 0,
+            count: [num_iterations; LANES],
+            z: Points::default(),
+        }
+    }
+
+    /// Records the last point of the `lanes` of `z`, reached after `count`
+    /// iterations, and their `status`.
+    fn record(&mut self, lanes: u8, count: u32, z: &Points, status: Status) {
+        for lane in (0..LANES).filter(|lane| lanes & (1 << lane) != 0) {
+            self.count[lane] = count;
+            self.z.re[lane] = z.re[lane];
+            self.z.im[lane] = z.im[lane];
+        }
+        match status {
+            Status::Escaped => self.escaped |= lanes,
+            Status::Periodic => self.periodic |= lanes,
+            Status::Bounded => {}
+        }
+    }
+}
+
+/// Steps after which Brent's cycle detection saves the current point of the
+/// orbits: after 1, 2, 4, ... steps since the last save. All lanes start
+/// together, so they share it.
+struct Schedule {
+    since_saved: u32,
+    save_after: u32,
+}
+
+impl Schedule {
+    fn new() -> Self {
+        Schedule {
+            since_saved: 0,
+            save_after: 1,
+        }
+    }
+
+    /// Counts a step, returning whether the point it reached is to be saved.
+    fn step(&mut self) -> bool {
+        self.since_saved += 1;
+        if self.since_saved < self.save_after {
+            return false;
+        }
+        self.since_saved = 0;
+        self.save_after = self.save_after.saturating_mul(2);
+        true
+    }
+}
+
+/// Whether [`mandelbrot_lanes`] can track `track`: only the last point of
+/// bounded orbits is supported.
+pub fn supports(track: Track) -> bool {
+    let orbit_tracked = track.min_distance || track.trap.is_some() || track.average.is_some();
+    !(track.derivative || track.cycle || orbit_tracked)
+}
+
+/// Same as [`mandelbrot_with`] for the [`LANES`] points `c`, when
+/// [`supports`] `track`.
+pub fn mandelbrot_lanes(
+    c: &[ComplexDouble; LANES],
+    num_iterations: u32,
+    escape_radius: f64,
+    track: Track,
+) -> [EscapeResult; LANES] {
+    assert!(supports(track), "{track:?} can't be tracked by lanes");
+    let points = Points {
+        re: c.map(|c| c.re),
+        im: c.map(|c| c.im),
+    };
+    let skipped = (0..LANES)
+        .filter(|&lane| !track.final_z && Mandelbrot.certainly_bounded(c[lane]))
+        .fold(0u8, |mask, lane| mask | (1 << lane));
+    let active = ALL_LANES & !skipped;
+    let radius = escape_radius * escape_radius;
+    //  No point is closer than 0 to an earlier one
+    let tolerance = match track.final_z {
+        true => 0.,
+        false => periodicity_tolerance::<f64>() * periodicity_tolerance::<f64>(),
+    };
+
+    let orbits = kernel()(&points, active, num_iterations, radius, tolerance);
+    std::array::from_fn(|lane| EscapeResult {
+        status: match (
+            orbits.escaped & (1 << lane) != 0,
+            (orbits.periodic | skipped) & (1 << lane) != 0,
+        ) {
+            (true, _) => Status::Escaped,
+            (false, true) => Status::Periodic,
+            (false, false) => Status::Bounded,
+        },
+        count: orbits.count[lane],
+        z: ComplexDouble::new(orbits.z.re[lane], orbits.z.im[lane]),
+        ..EscapeResult::default()
+    })
+}
+
+/// Same as [`iterate`](crate::render::iterate) for the Mandelbrot set, the
+/// pixels being iterated by [`mandelbrot_lanes`], when [`supports`] the
+/// track of `settings`.
+pub fn iterate_lanes(settings: &Settings, samples: (u32, u32)) -> Buffer<EscapeResult> {
+    let bailout = settings.bailout();
+    let track = settings.track();
+    let mut escapes = Buffer::new(samples.0, samples.1, EscapeResult::default());
+    escapes.fill_rows(settings.threads, |y, row| {
+        for (x, chunk) in (0..).step_by(LANES).zip(row.chunks_mut(LANES)) {
+            let c = std::array::from_fn(|lane| settings.view.point(x + lane as u32, y, samples));
+            let results = mandelbrot_lanes(&c, settings.iterations, bailout, track);
+            chunk.copy_from_slice(&results[..chunk.len()]);
+        }
+    });
+    escapes
+}
+
+/// Same as [`render`](crate::render::render) for the Mandelbrot set, the
+/// first sample of every pixel being iterated by [`mandelbrot_lanes`].
+///
+/// Falls back to the scalar kernel when the colorings track what lanes
+/// can't, or with subdivision, which iterates pixels one by one; settings
+/// that [validate](Settings::validate) rule both out.
+pub fn render_lanes(settings: &Settings, samples: (u32, u32)) -> Image {
+    if !supports(settings.track()) || settings.subdivide {
+        return render(settings, &Mandelbrot, samples);
+    }
+
+    let bailout = settings.bailout();
+    let track = settings.track();
+    let escapes = iterate_lanes(settings, samples);
+    shade_with(settings, Mandelbrot.degree(), &escapes, |x, y| {
+        let c = settings.view.sample(x, y, samples);
+        mandelbrot_with(&c, settings.iterations, bailout, track)
+    })
+}
+
+/// Iterates the `active` lanes of `c` up to `num_iterations` times, until
+/// their squared absolute value exceeds `radius` or their squared distance
+/// from the point saved by the [`Schedule`] falls below `tolerance`.
+type Kernel = fn(&Points, u8, u32, f64, f64) -> Orbits;
+
+/// Fastest kernel the CPU supports.
+fn kernel() -> Kernel {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx512f") {
+            return iterate_avx512;
+        }
+        if is_x86_feature_detected!("avx") {
+            return iterate_avx;
+        }
+    }
+    iterate_portable
+}
+
+fn iterate_portable(
+    c: &Points,
+    mut active: u8,
+    num_iterations: u32,
+    radius: f64,
+    tolerance: f64,
+) -> Orbits {
+    let mut orbits = Orbits::new(num_iterations);
+    orbits.record(!active, num_iterations, &Points::default(), Status::Bounded);
+    let mut z = Points::default();
+    let (mut saved, mut schedule) = (z, Schedule::new());
+
+    for count in 0..=num_iterations {
+        let mut escaped = 0u8;
+        for lane in 0..LANES {
+            let norm_sqr = z.re[lane] * z.re[lane] + z.im[lane] * z.im[lane];
+            escaped |= ((norm_sqr > radius) as u8) << lane;
+        }
+        escaped &= active;
+        orbits.record(escaped, count, &z, Status::Escaped);
+        active &= !escaped;
+        if active == 0 {
+            break;
+        }
+        if count == num_iterations {
+            orbits.record(active, count, &z, Status::Bounded);
+            break;
+        }
+
+        let mut periodic = 0u8;
+        for lane in 0..LANES {
+            let (re, im) = (z.re[lane], z.im[lane]);
+            let product = re * im;
+            z.re[lane] = (re * re - im * im) + c.re[lane];
+            z.im[lane] = (product + product) + c.im[lane];
+            let (re, im) = (z.re[lane] - saved.re[lane], z.im[lane] - saved.im[lane]);
+            periodic |= ((re * re + im * im < tolerance) as u8) << lane;
+        }
+        periodic &= active;
+        orbits.record(periodic, num_iterations, &z, Status::Periodic);
+        active &= !periodic;
+        if schedule.step() {
+            saved = z;
+        }
+    }
+    orbits
+}
+
+#[cfg(target_arch = "x86_64")]
+fn iterate_avx(c: &Points, active: u8, num_iterations: u32, radius: f64, tolerance: f64) -> Orbits {
+    assert!(is_x86_feature_detected!("avx"));
+    // SAFETY: the CPU supports AVX, as just checked
+    unsafe { iterate_avx_unchecked(c, active, num_iterations, radius, tolerance) }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx")]
+unsafe fn iterate_avx_unchecked(
+    c: &Points,
+    active: u8,
+    num_iterations: u32,
+    radius: f64,
+    tolerance: f64,
+) -> Orbits {
+    use std::arch::x86_64::*;
+
+    let mut orbits = Orbits::new(num_iterations);
+    orbits.record(!active, num_iterations, &Points::default(), Status::Bounded);
+    let radius = _mm256_set1_pd(radius);
+    let tolerance = _mm256_set1_pd(tolerance);
+
+    //  Two registers of 4 lanes, iterated one after the other
+    for half in 0..2 {
+        let offset = 4 * half;
+        // SAFETY: the arrays have 4 elements past `offset`
+        let (c_re, c_im) = unsafe {
+            (
+                _mm256_loadu_pd(c.re[offset..].as_ptr()),
+                _mm256_loadu_pd(c.im[offset..].as_ptr()),
+            )
+        };
+        let (mut re, mut im) = (_mm256_setzero_pd(), _mm256_setzero_pd());
+        let (mut saved_re, mut saved_im, mut schedule) = (re, im, Schedule::new());
+        let mut active = (active >> offset) & 0b1111;
+        let mut z = Points::default();
+
+        for count in 0..=num_iterations {
+            if active == 0 {
+                break;
+            }
+            let (re2, im2) = (_mm256_mul_pd(re, re), _mm256_mul_pd(im, im));
+            let norm_sqr = _mm256_add_pd(re2, im2);
+            let outside = _mm256_cmp_pd::<_CMP_GT_OQ>(norm_sqr, radius);
+            let escaped = _mm256_movemask_pd(outside) as u8 & active;
+            let bounded = match count == num_iterations {
+                true => active & !escaped,
+                false => 0,
+            };
+            if escaped | bounded != 0 {
+                // SAFETY: the arrays have 4 elements past `offset`
+                unsafe {
+                    _mm256_storeu_pd(z.re[offset..].as_mut_ptr(), re);
+                    _mm256_storeu_pd(z.im[offset..].as_mut_ptr(), im);
+                }
+                orbits.record(escaped << offset, count, &z, Status::Escaped);
+                orbits.record(bounded << offset, count, &z, Status::Bounded);
+            }
+            active &= !(escaped | bounded);
+
+            let product = _mm256_mul_pd(re, im);
+            re = _mm256_add_pd(_mm256_sub_pd(re2, im2), c_re);
+            im = _mm256_add_pd(_mm256_add_pd(product, product), c_im);
+
+            let (d_re, d_im) = (_mm256_sub_pd(re, saved_re), _mm256_sub_pd(im, saved_im));
+            let distance = _mm256_add_pd(_mm256_mul_pd(d_re, d_re), _mm256_mul_pd(d_im, d_im));
+            let close = _mm256_cmp_pd::<_CMP_LT_OQ>(distance, tolerance);
+            let periodic = _mm256_movemask_pd(close) as u8 & active;
+            if periodic != 0 {
+                // SAFETY: the arrays have 4 elements past `offset`
+                unsafe {
+                    _mm256_storeu_pd(z.re[offset..].as_mut_ptr(), re);
+                    _mm256_storeu_pd(z.im[offset..].as_mut_ptr(), im);
+                }
+                orbits.record(periodic << offset, num_iterations, &z, Status::Periodic);
+            }
+            active &= !periodic;
+            if schedule.step() {
+                (saved_re, saved_im) = (re, im);
+            }
+        }
+    }
+    orbits
+}
+
+#[cfg(target_arch = "x86_64")]
+fn iterate_avx512(
+    c: &Points,
+    active: u8,
+    num_iterations: u32,
+    radius: f64,
+    tolerance: f64,
+) -> Orbits {
+    assert!(is_x86_feature_detected!("avx512f"));
+    // SAFETY: the CPU supports AVX-512, as just checked
+    unsafe { iterate_avx512_unchecked(c, active, num_iterations, radius, tolerance) }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+unsafe fn iterate_avx512_unchecked(
+    c: &Points,
+    mut active: u8,
+    num_iterations: u32,
+    radius: f64,
+    tolerance: f64,
+) -> Orbits {
+    use std::arch::x86_64::*;
+
+    let mut orbits = Orbits::new(num_iterations);
+    orbits.record(!active, num_iterations, &Points::default(), Status::Bounded);
+    let radius = _mm512_set1_pd(radius);
+    let tolerance = _mm512_set1_pd(tolerance);
+    // SAFETY: the arrays have 8 elements
+    let (c_re, c_im) = unsafe {
+        (
+            _mm512_loadu_pd(c.re.as_ptr()),
+            _mm512_loadu_pd(c.im.as_ptr()),
+        )
+    };
+    let (mut re, mut im) = (_mm512_setzero_pd(), _mm512_setzero_pd());
+    let (mut saved_re, mut saved_im, mut schedule) = (re, im, Schedule::new());
+    let mut z = Points::default();
+
+    for count in 0..=num_iterations {
+        let (re2, im2) = (_mm512_mul_pd(re, re), _mm512_mul_pd(im, im));
+        let norm_sqr = _mm512_add_pd(re2, im2);
+        let escaped = _mm512_cmp_pd_mask::<_CMP_GT_OQ>(norm_sqr, radius) & active;
+        let bounded = match count == num_iterations {
+            true => active & !escaped,
+            false => 0,
+        };
+        if escaped | bounded != 0 {
+            // SAFETY: the arrays have 8 elements
+            unsafe {
+                _mm512_storeu_pd(z.re.as_mut_ptr(), re);
+                _mm512_storeu_pd(z.im.as_mut_ptr(), im);
+            }
+            orbits.record(escaped, count, &z, Status::Escaped);
+            orbits.record(bounded, count, &z, Status::Bounded);
+        }
+        active &= !(escaped | bounded);
+        if active == 0 {
+            break;
+        }
+
+        let product = _mm512_mul_pd(re, im);
+        re = _mm512_add_pd(_mm512_sub_pd(re2, im2), c_re);
+        im = _mm512_add_pd(_mm512_add_pd(product, product), c_im);
+
+        let (d_re, d_im) = (_mm512_sub_pd(re, saved_re), _mm512_sub_pd(im, saved_im));
+        let distance = _mm512_add_pd(_mm512_mul_pd(d_re, d_re), _mm512_mul_pd(d_im, d_im));
+        let periodic = _mm512_cmp_pd_mask::<_CMP_LT_OQ>(distance, tolerance) & active;
+        if periodic != 0 {
+            // SAFETY: the arrays have 8 elements
+            unsafe {
+                _mm512_storeu_pd(z.re.as_mut_ptr(), re);
+                _mm512_storeu_pd(z.im.as_mut_ptr(), im);
+            }
+            orbits.record(periodic, num_iterations, &z, Status::Periodic);
+        }
+        active &= !periodic;
+        if schedule.step() {
+            (saved_re, saved_im) = (re, im);
+        }
+    }
+    orbits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points of a grid over the whole set, in groups of [`LANES`].
+    fn groups() -> Vec<[ComplexDouble; LANES]> {
+        let points: Vec<_> = (0..48)
+            .flat_map(|i| {
+                (0..40).map(move |j| {
+                    ComplexDouble::new(
+                        -2.1 + 2.7 * (i as f64 + 0.5) / 48.,
+                        -1.2 + 2.4 * (j as f64 + 0.5) / 40.,
+                    )
+                })
+            })
+            .collect();
+        points
+            .chunks_exact(LANES)
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn scalar_test() {
+        let mut kernels: Vec<Kernel> = vec![iterate_portable];
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx") {
+                kernels.push(iterate_avx);
+            }
+            if is_x86_feature_detected!("avx512f") {
+                kernels.push(iterate_avx512);
+            }
+        }
+
+        let active = 0b1011_1111;
+        for c in groups() {
+            let points = Points {
+                re: c.map(|c| c.re),
+                im: c.map(|c| c.im),
+            };
+            for tolerance in [0., 1e-26] {
+                let expected = kernels[0](&points, active, 500, 4., tolerance);
+                for kernel in &kernels[1..] {
+                    assert_eq!(kernel(&points, active, 500, 4., tolerance), expected);
+                }
+                assert_eq!((expected.escaped | expected.periodic) & !active, 0);
+            }
+
+            for (lane, result) in mandelbrot_lanes(&c, 500, 2., Track::default())
+                .iter()
+                .enumerate()
+            {
+                let scalar = mandelbrot_with(&c[lane], 500, 2., Track::default());
+                assert_eq!(*result, scalar, "{}", c[lane]);
+            }
+        }
+    }
+
+    #[test]
+    fn bounded_test() {
+        //  Bounded lanes end on their last point, skipped ones on the origin
+        let mut c = [ComplexDouble::new(0.3, 0.); LANES];
+        c[2] = ComplexDouble::new(-0.1, 0.1);
+        c[5] = ComplexDouble::new(-1.3, 0.);
+        let results = mandelbrot_lanes(&c, 20, 2., Track::default());
+        assert_eq!(results[2].status, Status::Periodic);
+        assert_eq!(results[2].z, ComplexDouble::new(0., 0.));
+        assert!(results[0].escaped());
+
+        let final_z = Track {
+            final_z: true,
+            ..Track::default()
+        };
+        let results = mandelbrot_lanes(&c, 20, 2., final_z);
+        for lane in [2, 5] {
+            let scalar = mandelbrot_with(&c[lane], 20, 2., final_z);
+            assert_eq!(
+                (results[lane].status, results[lane].z),
+                (Status::Bounded, scalar.z)
+            );
+        }
+    }
+
+    #[test]
+    fn status_test() {
+        //  Periodic points outside the cardioid and the bulb are found too
+        let settings = Settings {
+            view: crate::view::View::new(-1.5..0.5, -1.2..1.2),
+            iterations: 1000,
+            ..Settings::default()
+        };
+        let samples = (90, 80);
+        let lanes = iterate_lanes(&settings, samples);
+        let scalar = crate::render::iterate(&settings, &Mandelbrot, samples);
+        let status = |escapes: &Buffer<EscapeResult>| {
+            escapes
+                .as_slice()
+                .iter()
+                .map(|result| result.status)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(status(&lanes), status(&scalar));
+        let found = (0..samples.1)
+            .flat_map(|y| (0..samples.0).map(move |x| (x, y)))
+            .filter(|&(x, y)| lanes[(x, y)].status == Status::Periodic)
+            .any(|(x, y)| !Mandelbrot.certainly_bounded(settings.view.point(x, y, samples)));
+        assert!(found);
+    }
+
+    #[test]
+    fn render_test() {
+        //  A width that isn't a multiple of the lanes leaves a partial group
+        let settings = Settings {
+            view: crate::view::View::new(-0.8..-0.7, 0.05..0.15),
+            iterations: 500,
+            supersampling: 2,
+            ..Settings::default()
+        };
+        let samples = (67, 41);
+        assert!(render_lanes(&settings, samples) == render(&settings, &Mandelbrot, samples));
+    }
+}
@@ -0,0 +1,316 @@
+//! Mariani–Silver rectangle subdivision.
+//!
+//! Instead of iterating every pixel, the image is cut into tiles whose
+//! borders are iterated first. A tile whose border pixels would all get the
+//! same color is filled without iterating its inside; any other tile is split
+//! in two along its longer side and the halves are handled the same way,
+//! reusing the pixels on the dividing line.
+//!
+//! Since the Mandelbrot set is connected and has no holes, a rectangle
+//! bordered by points of the set lies inside the set. Only points known to be
+//! in the set, with [`Status::Periodic`](crate::escape::Status::Periodic),
+//! count: points that merely didn't escape within the iteration limit may
+//! surround filaments that do. Bands of equal escape count are filled the
+//! same way, which is exact on all views tried but could in principle miss
+//! details smaller than a tile, as could fractals with holes.
+
+use crate::buffer::Buffer;
+use crate::color::{Coloring, InteriorColoring};
+use crate::escape::{escape_with, EscapeResult, Status, Track};
+use crate::fractal::Fractal;
+use crate::render::Settings;
+
+/// Side length of the tiles the image is cut into before subdividing.
+pub const TILE_SIZE: u32 = 64;
+
+/// Same as [`iterate`](crate::render::iterate), but fills uniform
+/// rectangles without iterating their inside.
+pub fn iterate_subdivided<F: Fractal + Sync>(
+    settings: &Settings,
+    fractal: &F,
+    samples: (u32, u32),
+) -> Buffer<EscapeResult> {
+    subdivide(settings, fractal, samples).0
+}
+
+/// Escape results of [`iterate_subdivided`] together with the number of
+/// pixels that were actually iterated.
+fn subdivide<F: Fractal + Sync>(
+    settings: &Settings,
+    fractal: &F,
+    samples: (u32, u32),
+) -> (Buffer<EscapeResult>, u64) {
+    let bailout = settings.bailout();
+    let track = settings.track();
+    subdivide_with(settings, samples, |x, y| {
+        let c = settings.view.point(x, y, samples);
+        escape_with(fractal, &c, settings.iterations, bailout, track)
+    })
+}
+
+/// Same as [`subdivide`] with the escape result of pixel `(x, y)` given by
+/// `compute`.
+pub(crate) fn subdivide_with<C>(
+    settings: &Settings,
+    samples: (u32, u32),
+    compute: C,
+) -> (Buffer<EscapeResult>, u64)
+where
+    C: Fn(u32, u32) -> EscapeResult + Sync,
+{
+    let tiles = (samples.0.div_ceil(TILE_SIZE), samples.1.div_ceil(TILE_SIZE));
+
+    //  Every tile of a row of tiles is computed into its own buffer
+    let mut tile_rows = Buffer::new(tiles.0, tiles.1, (Buffer::new(0, 0, None), 0));
+    tile_rows.fill_rows(settings.threads, |tile_y, row| {
+        for (tile_x, (results, computed)) in (0..).zip(row) {
+            let origin = (tile_x * TILE_SIZE, tile_y * TILE_SIZE);
+            let size = (
+                TILE_SIZE.min(samples.0 - origin.0),
+                TILE_SIZE.min(samples.1 - origin.1),
+            );
+            let mut tile = Tile {
+                results: vec![None; (size.0 * size.1) as usize],
+                width: size.0,
+                computed: 0,
+                compute: |x, y| compute(origin.0 + x, origin.1 + y),
+                uniform: |a: &EscapeResult, b: &EscapeResult| same_color(settings, a, b),
+            };
+            tile.fill(0, 0, size.0 - 1, size.1 - 1);
+
+            *computed = tile.computed;
+            *results = Buffer::from_vec(size.0, size.1, tile.results);
+        }
+    });
+
+    let computed = tile_rows
+        .as_slice()
+        .iter()
+        .map(|(_, computed)| computed)
+        .sum();
+    let mut escapes = Buffer::new(samples.0, samples.1, EscapeResult::default());
+    escapes.fill_rows(settings.threads, |y, row| {
+        for (x, result) in (0..).zip(row) {
+            let (tile, _) = &tile_rows[(x / TILE_SIZE, y / TILE_SIZE)];
+            *result = tile[(x % TILE_SIZE, y % TILE_SIZE)].expect("every pixel is filled");
+        }
+    });
+    (escapes, computed)
+}
+
+/// Whether the colors of the render only depend on the escape count of the
+/// escaping samples and on the status of the others.
+fn count_only(settings: &Settings) -> bool {
+    settings.coloring == Coloring::EscapeTime
+        && settings.interior == InteriorColoring::Black
+        //  Nothing else about the orbits
+        && settings.track() == Track::default()
+}
+
+/// Whether two samples are colored alike whatever else is known about the
+/// render, so that one can stand in for the other.
+fn same_color(settings: &Settings, a: &EscapeResult, b: &EscapeResult) -> bool {
+    if !count_only(settings) {
+        return false;
+    }
+    match (a.escaped(), b.escaped()) {
+        (false, false) => a.status == Status::Periodic && b.status == Status::Periodic,
+        (true, true) => a.count == b.count,
+        _ => false,
+    }
+}
+
+/// Rectangle of the image being subdivided.
+struct Tile<C, U> {
+    /// Results of the pixels computed or filled so far, row by row.
+    results: Vec<Option<EscapeResult>>,
+    width: u32,
+    /// Number of pixels iterated.
+    computed: u64,
+    compute: C,
+    uniform: U,
+}
+
+impl<C, U> Tile<C, U>
+where
+    C: Fn(u32, u32) -> EscapeResult,
+    U: Fn(&EscapeResult, &EscapeResult) -> bool,
+{
+    fn get(&mut self, x: u32, y: u32) -> EscapeResult {
+        let index = (y * self.width + x) as usize;
+        match self.results[index] {
+            Some(result) => result,
+            None => {
+                let result = (self.compute)(x, y);
+                self.computed += 1;
+                self.results[index] = Some(result);
+                result
+            }
+        }
+    }
+
+    /// Fills the pixels from `(x0, y0)` to `(x1, y1)`, both inclusive.
+    fn fill(&mut self, x0: u32, y0: u32, x1: u32, y1: u32) {
+        let first = self.get(x0, y0);
+        let mut uniform = true;
+        for x in x0..=x1 {
+            for y in [y0, y1] {
+                let result = self.get(x, y);
+                uniform &= (self.uniform)(&first, &result);
+            }
+        }
+        for y in y0..=y1 {
+            for x in [x0, x1] {
+                let result = self.get(x, y);
+                uniform &= (self.uniform)(&first, &result);
+            }
+        }
+
+        if x1 - x0 < 2 || y1 - y0 < 2 {
+            //  Only border pixels
+        } else if uniform {
+            for y in y0 + 1..y1 {
+                for x in x0 + 1..x1 {
+                    self.results[(y * self.width + x) as usize] = Some(first);
+                }
+            }
+        } else if x1 - x0 >= y1 - y0 {
+            let middle = (x0 + x1) / 2;
+            self.fill(x0, y0, middle, y1);
+            self.fill(middle, y0, x1, y1);
+        } else {
+            let middle = (y0 + y1) / 2;
+            self.fill(x0, y0, x1, middle);
+            self.fill(x0, middle, x1, y1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::escape::ComplexDouble;
+    use crate::fractal::{Julia, Mandelbrot};
+    use crate::lighting::Lighting;
+    use crate::render::{iterate, render, shade};
+    use crate::trap::OrbitTrap;
+    use crate::view::View;
+
+    #[test]
+    fn identical_test() {
+        let views = [
+            View::default(),
+            View::new(-0.8..-0.7, 0.05..0.15),
+            View::centered(ComplexDouble::new(-1.768, 0.), 100., 1.5),
+        ];
+        for view in views {
+            let settings = Settings {
+                view,
+                iterations: 300,
+                threads: 2,
+                ..Settings::default()
+            };
+            let samples = (200, 150);
+            let (escapes, computed) = subdivide(&settings, &Mandelbrot, samples);
+
+            assert!(
+                shade(&settings, &Mandelbrot, &escapes) == render(&settings, &Mandelbrot, samples),
+                "{:?}",
+                settings.view
+            );
+            assert!(computed < 200 * 150);
+        }
+    }
+
+    #[test]
+    fn uniform_test() {
+        //  A view inside the main cardioid only needs the borders of the tiles
+        let settings = Settings {
+            view: View::new(-0.2..0.1, -0.1..0.1),
+            ..Settings::default()
+        };
+        let (escapes, computed) = subdivide(&settings, &Mandelbrot, (128, 64));
+        assert_eq!(computed, 2 * (4 * 64 - 4));
+        assert!(escapes.as_slice().iter().all(|result| !result.escaped()));
+
+        //  Colorings that tell apart points of equal count iterate every pixel
+        let settings = Settings {
+            coloring: Coloring::Smooth,
+            ..Settings::default()
+        };
+        let julia = Julia {
+            c: ComplexDouble::new(-0.4, 0.6),
+        };
+        let (escapes, _) = subdivide(&settings, &julia, (70, 65));
+        let exact = iterate(&settings, &julia, (70, 65));
+        for (a, b) in escapes.as_slice().iter().zip(exact.as_slice()) {
+            assert!(a == b || !(a.escaped() || b.escaped()));
+        }
+        assert!(shade(&settings, &julia, &escapes) == shade(&settings, &julia, &exact));
+    }
+
+    #[test]
+    fn coloring_test() {
+        //  Only count-only colorings fill rectangles, and none changes colors
+        let cases = [
+            (Settings::default(), true),
+            (
+                Settings {
+                    coloring: Coloring::Smooth,
+                    ..Settings::default()
+                },
+                false,
+            ),
+            (
+                Settings {
+                    coloring: Coloring::Distance { thickness: 1. },
+                    ..Settings::default()
+                },
+                false,
+            ),
+            (
+                Settings {
+                    interior: InteriorColoring::Period,
+                    ..Settings::default()
+                },
+                false,
+            ),
+            (
+                Settings {
+                    trap: Some(OrbitTrap::Point {
+                        center: ComplexDouble::new(0., 0.),
+                    }),
+                    ..Settings::default()
+                },
+                false,
+            ),
+            (
+                Settings {
+                    coloring: Coloring::StripeAverage { density: 5. },
+                    ..Settings::default()
+                },
+                false,
+            ),
+            (
+                Settings {
+                    lighting: Some(Lighting::default()),
+                    ..Settings::default()
+                },
+                false,
+            ),
+        ];
+        for (settings, filled) in cases {
+            let settings = Settings {
+                threads: 2,
+                ..settings
+            };
+            let samples = (200, 150);
+            let (escapes, computed) = subdivide(&settings, &Mandelbrot, samples);
+            assert_eq!(count_only(&settings), filled);
+            assert_eq!(computed < 200 * 150, filled);
+            assert!(
+                shade(&settings, &Mandelbrot, &escapes) == render(&settings, &Mandelbrot, samples)
+            );
+        }
+    }
+}
@@ -0,0 +1,443 @@
+//! Orbit traps: shapes in the plane that color a point by how close its
+//! orbit comes to them.
+//!
+//! An [`OrbitTrap`] colors escaping and bounded points alike. The closest
+//! point of the orbit to the trap, its [`TrapHit`], is tracked during the
+//! iteration. Shape traps map its distance onto the palette. Image traps
+//! paint the pixel of a picture laid onto the plane at the first point of
+//! the orbit that lands on it; orbits that never do keep their usual color.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use image::RgbaImage;
+use plotters::style::RGBColor;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::escape::ComplexDouble;
+use crate::palette::{ColorSpace, Palette};
+
+/// Distance of the orbit from a shape trap that is mapped to the middle of
+/// the palette.
+pub const TRAP_DISTANCE_SCALE: f64 = 0.25;
+
+fn default_size() -> f64 {
+    1.
+}
+
+/// Trap shape and its placement in the plane. Angles are in degrees,
+/// counterclockwise from the real axis.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum OrbitTrap {
+    /// Single point.
+    Point {
+        #[serde(default)]
+        center: ComplexDouble,
+    },
+    /// Straight line through `center`.
+    Line {
+        #[serde(default)]
+        center: ComplexDouble,
+        #[serde(default)]
+        angle: f64,
+    },
+    /// Two perpendicular lines crossing at `center`, the first one at
+    /// `angle`.
+    Cross {
+        #[serde(default)]
+        center: ComplexDouble,
+        #[serde(default)]
+        angle: f64,
+    },
+    /// Circle of radius `radius`.
+    Circle {
+        #[serde(default)]
+        center: ComplexDouble,
+        #[serde(default = "default_size")]
+        radius: f64,
+    },
+    /// Picture centered on `center` and `width` wide, keeping its aspect
+    /// ratio. Transparent pixels let the usual color show through.
+    Image {
+        image: TrapImage,
+        #[serde(default)]
+        center: ComplexDouble,
+        #[serde(default = "default_size")]
+        width: f64,
+    },
+}
+
+impl OrbitTrap {
+    /// Shape that is tracked along the orbits.
+    pub fn shape(&self) -> TrapShape {
+        let direction = |angle: f64| ComplexDouble::from_polar(1., angle.to_radians());
+        match *self {
+            OrbitTrap::Point { center } => TrapShape::Point { center },
+            OrbitTrap::Line { center, angle } => TrapShape::Line {
+                center,
+                direction: direction(angle),
+            },
+            OrbitTrap::Cross { center, angle } => TrapShape::Cross {
+                center,
+                direction: direction(angle),
+            },
+            OrbitTrap::Circle { center, radius } => TrapShape::Circle { center, radius },
+            OrbitTrap::Image {
+                ref image,
+                center,
+                width,
+            } => {
+                let (columns, rows) = image.dimensions();
+                TrapShape::Rectangle {
+                    center,
+                    half_size: (width / 2., width * rows as f64 / columns as f64 / 2.),
+                }
+            }
+        }
+    }
+
+    /// Checks the parameters of the trap.
+    pub fn validate(&self) -> Result<(), String> {
+        let (center, size) = match *self {
+            OrbitTrap::Point { center }
+            | OrbitTrap::Line { center, .. }
+            | OrbitTrap::Cross { center, .. } => (center, 1.),
+            OrbitTrap::Circle { center, radius } => (center, radius),
+            OrbitTrap::Image { center, width, .. } => (center, width),
+        };
+        if !(center.re.is_finite() && center.im.is_finite()) {
+            return Err(format!("trap center {} is not finite", center));
+        }
+        if let OrbitTrap::Line { angle, .. } | OrbitTrap::Cross { angle, .. } = *self {
+            if !angle.is_finite() {
+                return Err(format!("trap angle {} is not finite", angle));
+            }
+        }
+        if !(size > 0. && size.is_finite()) {
+            return Err(format!("trap size {} is not a positive number", size));
+        }
+        if let OrbitTrap::Image { image, .. } = self {
+            if !image.is_loaded() {
+                return Err(format!(
+                    "trap image {} is not loaded",
+                    image.path().display()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Color of a point whose orbit came closest to the trap at `hit`, or
+    /// `fallback` for image traps where the orbit missed the picture and for
+    /// orbits that escaped before their first step.
+    pub fn color(&self, palette: &Palette, hit: TrapHit, fallback: RGBColor) -> RGBColor {
+        if !hit.distance.is_finite() {
+            return fallback;
+        }
+        let OrbitTrap::Image { image, .. } = self else {
+            let distance = hit.distance;
+            return palette.color(distance / (distance + TRAP_DISTANCE_SCALE));
+        };
+        let TrapShape::Rectangle { center, half_size } = self.shape() else {
+            unreachable!("image traps are rectangles");
+        };
+        if hit.distance > 0. {
+            return fallback;
+        }
+        //  Rows of the picture run from the top down
+        let u = (hit.z.re - center.re + half_size.0) / (2. * half_size.0);
+        let v = (center.im + half_size.1 - hit.z.im) / (2. * half_size.1);
+        let (color, alpha) = image.pixel(u, v);
+        ColorSpace::Srgb.mix(fallback, color, alpha)
+    }
+}
+
+/// Geometry of an [`OrbitTrap`], see [`OrbitTrap::shape`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrapShape {
+    Point {
+        center: ComplexDouble,
+    },
+    /// Line through `center` along the unit vector `direction`.
+    Line {
+        center: ComplexDouble,
+        direction: ComplexDouble,
+    },
+    /// Lines through `center` along and across the unit vector `direction`.
+    Cross {
+        center: ComplexDouble,
+        direction: ComplexDouble,
+    },
+    Circle {
+        center: ComplexDouble,
+        radius: f64,
+    },
+    /// Filled rectangle, half as wide and tall as `half_size`.
+    Rectangle {
+        center: ComplexDouble,
+        half_size: (f64, f64),
+    },
+}
+
+impl TrapShape {
+    /// Distance from `z` to the shape.
+    pub fn distance(&self, z: ComplexDouble) -> f64 {
+        match *self {
+            TrapShape::Point { center } => (z - center).norm(),
+            TrapShape::Line { center, direction } => ((z - center) * direction.conj()).im.abs(),
+            TrapShape::Cross { center, direction } => {
+                let z = (z - center) * direction.conj();
+                z.re.abs().min(z.im.abs())
+            }
+            TrapShape::Circle { center, radius } => ((z - center).norm() - radius).abs(),
+            TrapShape::Rectangle { center, half_size } => {
+                let x = ((z.re - center.re).abs() - half_size.0).max(0.);
+                let y = ((z.im - center.im).abs() - half_size.1).max(0.);
+                x.hypot(y)
+            }
+        }
+    }
+}
+
+/// Closest point of an orbit to a trap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrapHit {
+    /// Distance from the trap, infinite if the orbit has no points yet.
+    pub distance: f64,
+    /// First point of the orbit at that distance.
+    pub z: ComplexDouble,
+}
+
+impl Default for TrapHit {
+    fn default() -> Self {
+        TrapHit {
+            distance: f64::INFINITY,
+            z: ComplexDouble::new(0., 0.),
+        }
+    }
+}
+
+impl TrapHit {
+    /// Closest point to `shape` after the orbit went on to `z`.
+    pub fn update(self, shape: &TrapShape, z: ComplexDouble) -> TrapHit {
+        let distance = shape.distance(z);
+        match distance < self.distance {
+            true => TrapHit { distance, z },
+            false => self,
+        }
+    }
+}
+
+/// Picture of an image trap, stored in scenes as the path it is loaded from.
+///
+/// Deserializing only reads the path; [`TrapImage::resolve`] then loads the
+/// picture, as [`Scene::load`](crate::scene::Scene::load) does relative to
+/// the directory of the scene file.
+#[derive(Clone)]
+pub struct TrapImage {
+    path: PathBuf,
+    /// `None` until loaded.
+    pixels: Option<Arc<RgbaImage>>,
+}
+
+impl TrapImage {
+    /// Loads the PNG file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, image::ImageError> {
+        let path = path.as_ref();
+        let pixels = image::open(path)?.into_rgba8();
+        Ok(TrapImage {
+            path: path.to_path_buf(),
+            pixels: Some(Arc::new(pixels)),
+        })
+    }
+
+    /// Image made of `pixels`, as if loaded from `path`.
+    pub fn new<P: AsRef<Path>>(path: P, pixels: RgbaImage) -> Self {
+        TrapImage {
+            path: path.as_ref().to_path_buf(),
+            pixels: Some(Arc::new(pixels)),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.pixels.is_some()
+    }
+
+    /// Loads the picture from the path, taken relative to `dir` if it is
+    /// relative. The path is kept as written, so that scenes are stored
+    /// unchanged.
+    pub fn resolve(&mut self, dir: &Path) -> Result<(), image::ImageError> {
+        let pixels = image::open(dir.join(&self.path))?.into_rgba8();
+        self.pixels = Some(Arc::new(pixels));
+        Ok(())
+    }
+
+    /// Size of the picture in pixels, `(1, 1)` until it is loaded.
+    fn dimensions(&self) -> (u32, u32) {
+        self.pixels
+            .as_ref()
+            .map_or((1, 1), |pixels| pixels.dimensions())
+    }
+
+    /// Color and opacity in `0..=1` of the pixel at `(u, v)`, coordinates in
+    /// `0..=1` from the top-left corner. Pictures that aren't loaded are
+    /// transparent.
+    fn pixel(&self, u: f64, v: f64) -> (RGBColor, f64) {
+        let Some(pixels) = &self.pixels else {
+            return (RGBColor(0, 0, 0), 0.);
+        };
+        let (columns, rows) = pixels.dimensions();
+        let x = ((u * columns as f64) as u32).min(columns - 1);
+        let y = ((v * rows as f64) as u32).min(rows - 1);
+        let [r, g, b, a] = pixels.get_pixel(x, y).0;
+        (RGBColor(r, g, b), a as f64 / 255.)
+    }
+}
+
+impl fmt::Debug for TrapImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("TrapImage").field(&self.path).finish()
+    }
+}
+
+impl PartialEq for TrapImage {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.pixels == other.pixels
+    }
+}
+
+impl Serialize for TrapImage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.path.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TrapImage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(TrapImage {
+            path: PathBuf::deserialize(deserializer)?,
+            pixels: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::escape::{escape_with, Status, Track};
+    use crate::fractal::Julia;
+    use image::Rgba;
+
+    #[test]
+    fn distance_test() {
+        let z = ComplexDouble::new(3., 4.);
+        let origin = ComplexDouble::new(0., 0.);
+        let shape = |trap: OrbitTrap| trap.shape();
+
+        assert_eq!(shape(OrbitTrap::Point { center: origin }).distance(z), 5.);
+        let line = OrbitTrap::Line {
+            center: ComplexDouble::new(0., 1.),
+            angle: 0.,
+        };
+        assert_eq!(shape(line).distance(z), 3.);
+        let diagonal = OrbitTrap::Line {
+            center: origin,
+            angle: 45.,
+        };
+        assert!((shape(diagonal).distance(z) - 0.5f64.sqrt()).abs() < 1e-12);
+        let cross = OrbitTrap::Cross {
+            center: origin,
+            angle: 90.,
+        };
+        assert!((shape(cross).distance(z) - 3.).abs() < 1e-12);
+        let circle = OrbitTrap::Circle {
+            center: origin,
+            radius: 2.,
+        };
+        assert_eq!(shape(circle).distance(z), 3.);
+
+        let rectangle = TrapShape::Rectangle {
+            center: origin,
+            half_size: (3., 1.),
+        };
+        assert_eq!(rectangle.distance(z), 3.);
+        assert_eq!(rectangle.distance(ComplexDouble::new(-2., 0.5)), 0.);
+    }
+
+    #[test]
+    fn hit_test() {
+        let shape = TrapShape::Point {
+            center: ComplexDouble::new(1., 0.),
+        };
+        let orbit = [(0., 0.), (1., 1.), (2., 0.), (3., 3.)];
+        let hit = orbit.iter().fold(TrapHit::default(), |hit, &(re, im)| {
+            hit.update(&shape, ComplexDouble::new(re, im))
+        });
+        assert_eq!(hit.distance, 1.);
+        assert_eq!(hit.z, ComplexDouble::new(0., 0.));
+    }
+
+    #[test]
+    fn escaped_test() {
+        //  The orbit of a Julia set starts outside the escape radius and
+        //  never gets near the trap
+        let trap = OrbitTrap::Point {
+            center: ComplexDouble::new(0., 0.),
+        };
+        let julia = Julia {
+            c: ComplexDouble::new(0., 0.),
+        };
+        let track = Track {
+            trap: Some(trap.shape()),
+            ..Track::default()
+        };
+        let result = escape_with(&julia, &ComplexDouble::new(3., 0.), 50, 2., track);
+        assert_eq!((result.status, result.count), (Status::Escaped, 0));
+        let hit = result.trap.unwrap();
+        assert_eq!(hit, TrapHit::default());
+
+        let fallback = RGBColor(0, 255, 0);
+        assert_eq!(trap.color(&Palette::default(), hit, fallback), fallback);
+    }
+
+    #[test]
+    fn image_test() {
+        //  Left half red, right half transparent
+        let pixels = RgbaImage::from_fn(4, 2, |x, _| match x < 2 {
+            true => Rgba([255, 0, 0, 255]),
+            false => Rgba([0, 0, 255, 0]),
+        });
+        let trap = OrbitTrap::Image {
+            image: TrapImage::new("trap.png", pixels),
+            center: ComplexDouble::new(0., 0.),
+            width: 2.,
+        };
+        assert_eq!(
+            trap.shape(),
+            TrapShape::Rectangle {
+                center: ComplexDouble::new(0., 0.),
+                half_size: (1., 0.5)
+            }
+        );
+
+        let palette = Palette::default();
+        let fallback = RGBColor(0, 255, 0);
+        let hit = |re, im| TrapHit {
+            distance: trap.shape().distance(ComplexDouble::new(re, im)),
+            z: ComplexDouble::new(re, im),
+        };
+        assert_eq!(
+            trap.color(&palette, hit(-0.5, 0.2), fallback),
+            RGBColor(255, 0, 0)
+        );
+        assert_eq!(trap.color(&palette, hit(0.5, 0.2), fallback), fallback);
+        assert_eq!(trap.color(&palette, hit(0.5, 0.7), fallback), fallback);
+        assert!(TrapImage::load("does-not-exist.png").is_err());
+    }
+}
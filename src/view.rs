@@ -0,0 +1,226 @@
+//! Region of the complex plane that is sampled onto the pixel grid.
+
+use std::ops::Range;
+
+use num::complex::Complex;
+use serde::{Deserialize, Serialize};
+
+use crate::bignum::{ComplexDecimal, Decimal};
+use crate::escape::ComplexDouble;
+use crate::precision::Real;
+
+/// Half of the imaginary extent of the view at zoom `1`.
+pub const DEFAULT_RADIUS: f64 = 1.2;
+
+/// Largest absolute value of the coordinates of the center, which keeps
+/// them within reach of the [`Fixed`](crate::bignum::Fixed) numbers of
+/// reference orbits.
+pub const MAX_COORDINATE: f64 = 1e18;
+
+/// Rectangular window of the complex plane.
+///
+/// The window is centered on `center`, reaches `radius` above and below it
+/// along the imaginary axis and `radius * aspect` left and right of it along
+/// the real axis. Pixel rows run from the top of the window to the bottom.
+///
+/// The center and radius are exact decimals, so that deep-zoom locations
+/// keep all their digits; most computations use their `f64` approximations.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct View {
+    pub center: ComplexDecimal,
+    pub radius: Decimal,
+    pub aspect: f64,
+}
+
+impl Default for View {
+    /// The classic full view of the set.
+    fn default() -> Self {
+        View::new(-2.1..0.6, -1.2..1.2)
+    }
+}
+
+impl View {
+    /// View spanning `re` horizontally and `im` vertically.
+    pub fn new(re: Range<f64>, im: Range<f64>) -> Self {
+        let radius = (im.end - im.start) / 2.;
+        View {
+            center: ComplexDecimal::new(
+                Decimal::from_f64((re.start + re.end) / 2.),
+                Decimal::from_f64((im.start + im.end) / 2.),
+            ),
+            radius: Decimal::from_f64(radius),
+            aspect: (re.end - re.start) / 2. / radius,
+        }
+    }
+
+    /// View around `center` magnified `zoom` times relative to
+    /// [`DEFAULT_RADIUS`], with a width to height ratio of `aspect`.
+    pub fn centered(center: ComplexDouble, zoom: f64, aspect: f64) -> Self {
+        View {
+            center: ComplexDecimal::new(Decimal::from_f64(center.re), Decimal::from_f64(center.im)),
+            radius: Decimal::from_f64(DEFAULT_RADIUS / zoom),
+            aspect,
+        }
+    }
+
+    /// Closest `f64` point to the center.
+    pub fn center_f64(&self) -> ComplexDouble {
+        ComplexDouble::new(self.center.re.to_f64(), self.center.im.to_f64())
+    }
+
+    /// Center in the precision `T`.
+    pub fn center_as<T: Real>(&self) -> Complex<T> {
+        Complex::new(
+            T::from_decimal(&self.center.re),
+            T::from_decimal(&self.center.im),
+        )
+    }
+
+    /// Extent of the view along the real axis.
+    pub fn re(&self) -> Range<f64> {
+        let (center, half_width) = (self.center.re.to_f64(), self.radius.to_f64() * self.aspect);
+        (center - half_width)..(center + half_width)
+    }
+
+    /// Extent of the view along the imaginary axis.
+    pub fn im(&self) -> Range<f64> {
+        let (center, radius) = (self.center.im.to_f64(), self.radius.to_f64());
+        (center - radius)..(center + radius)
+    }
+
+    /// Distance between neighbouring samples along each axis when the view
+    /// is sampled with `samples` points horizontally and vertically.
+    pub fn step(&self, samples: (u32, u32)) -> (f64, f64) {
+        let radius = self.radius.to_f64();
+        (
+            2. * radius * self.aspect / samples.0 as f64,
+            2. * radius / samples.1 as f64,
+        )
+    }
+
+    /// Point of the complex plane at the center of pixel `(x, y)`, counted
+    /// from the top-left corner of a grid of `samples` pixels.
+    pub fn point(&self, x: u32, y: u32, samples: (u32, u32)) -> ComplexDouble {
+        self.sample(x as f64 + 0.5, y as f64 + 0.5, samples)
+    }
+
+    /// Point of the complex plane at the fractional pixel coordinates
+    /// `(x, y)`, where pixel `(0, 0)` covers `0..1` along both axes; used to
+    /// take several samples inside one pixel.
+    pub fn sample(&self, x: f64, y: f64, samples: (u32, u32)) -> ComplexDouble {
+        let step = self.step(samples);
+        ComplexDouble::new(self.re().start + step.0 * x, self.im().end - step.1 * y)
+    }
+
+    /// Same as [`View::sample`] relative to the center of the view, which
+    /// stays accurate when the view is too small for `f64` to tell its points
+    /// apart.
+    pub fn offset(&self, x: f64, y: f64, samples: (u32, u32)) -> ComplexDouble {
+        let (step, radius) = (self.step(samples), self.radius.to_f64());
+        ComplexDouble::new(step.0 * x - radius * self.aspect, radius - step.1 * y)
+    }
+
+    /// Same as [`View::sample`] in the precision `T`, adding the offset from
+    /// the center in that precision so that views too small for `f64` to
+    /// tell their points apart still can be sampled.
+    ///
+    /// Converting the center is slow for precise types; when sampling many
+    /// points, add the offsets to [`View::center_as`] instead.
+    pub fn sample_as<T: Real>(&self, x: f64, y: f64, samples: (u32, u32)) -> Complex<T> {
+        let offset = self.offset(x, y, samples);
+        self.center_as::<T>() + Complex::new(T::from_f64(offset.re), T::from_f64(offset.im))
+    }
+
+    /// Checks that the view is a finite, non-empty window.
+    pub fn validate(&self) -> Result<(), String> {
+        let center = self.center_f64();
+        if !(center.re.abs() <= MAX_COORDINATE && center.im.abs() <= MAX_COORDINATE) {
+            return Err(format!(
+                "view center {},{} is further than {} from the origin",
+                self.center.re, self.center.im, MAX_COORDINATE
+            ));
+        }
+        if self.radius.is_negative() || self.radius.is_zero() {
+            return Err(format!("view radius {} is not positive", self.radius));
+        }
+        let radius = self.radius.to_f64();
+        if !(radius.is_normal() && radius.is_finite()) {
+            return Err(format!(
+                "view radius {} is out of the range of f64",
+                self.radius
+            ));
+        }
+        if !(self.aspect.is_finite() && self.aspect > 0.) {
+            return Err(format!(
+                "view aspect {} is not a positive number",
+                self.aspect
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bignum::Fixed;
+    use crate::double_double::ComplexDoubleDouble;
+
+    #[test]
+    fn point_test() {
+        let view = View::new(-2.0..2.0, -1.0..1.0);
+        let samples = (4, 2);
+
+        assert_eq!(view.step(samples), (1.0, 1.0));
+        assert_eq!(view.point(0, 0, samples), ComplexDouble::new(-1.5, 0.5));
+        assert_eq!(view.point(3, 1, samples), ComplexDouble::new(1.5, -0.5));
+        assert_eq!(view.sample(3., 1., samples), ComplexDouble::new(1.0, 0.0));
+        assert_eq!(view.offset(3., 1., samples), ComplexDouble::new(1.0, 0.0));
+
+        let tiny = View::centered(ComplexDouble::new(-1.5, 0.), 1e25, 1.);
+        let (left, right) = (tiny.point(0, 0, samples), tiny.point(1, 0, samples));
+        assert_eq!(left, right);
+        let left: ComplexDoubleDouble = tiny.sample_as(0., 0., samples);
+        let right: ComplexDoubleDouble = tiny.sample_as(1., 0., samples);
+        assert_eq!((right.re - left.re).to_f64(), tiny.step(samples).0);
+
+        //  Centers between f64 points shift the samples by less than an ulp
+        let mut shifted = tiny.clone();
+        shifted.center.re = "-1.49999999999999999999999997".parse().unwrap();
+        assert_eq!(shifted.center_f64(), tiny.center_f64());
+        let left_shifted: ComplexDoubleDouble = shifted.sample_as(0., 0., samples);
+        assert!(((left_shifted.re - left.re).to_f64() - 3e-26).abs() < 1e-31);
+    }
+
+    #[test]
+    fn centered_test() {
+        let view = View::centered(ComplexDouble::new(-0.5, 0.25), 2.4, 1.5);
+
+        assert_eq!(view.im(), -0.25..0.75);
+        assert_eq!(view.re(), -1.25..0.25);
+    }
+
+    #[test]
+    fn validate_test() {
+        assert!(View::default().validate().is_ok());
+
+        //  Valid centers fit in the fixed-point numbers of reference orbits
+        let mut view = View::default();
+        view.center.re = "-1000000000000000000.000000000000000000001"
+            .parse()
+            .unwrap();
+        assert!(view.validate().is_ok());
+        assert!(Fixed::from_decimal(&view.center.re, 256).is_some());
+        view.center.re = "1e19".parse().unwrap();
+        assert!(view.validate().is_err());
+
+        for radius in ["0", "-1", "1e-400"] {
+            let view = View {
+                radius: radius.parse().unwrap(),
+                ..View::default()
+            };
+            assert!(view.validate().is_err(), "{}", radius);
+        }
+    }
+}